@@ -0,0 +1,98 @@
+use clap::Parser;
+use sales_model::csv::{self, CsvOptions, Quoting};
+use sales_model::{Format, TimestampFormat};
+
+/// Converts sales data between JSON, JSON Lines, XML, TOML and CSV.
+#[derive(Parser, Debug)]
+struct Args {
+    #[arg(long)]
+    input_path: Option<std::path::PathBuf>,
+
+    #[arg(long)]
+    output_path: Option<std::path::PathBuf>,
+
+    /// Input format, guessed from the input extension when omitted
+    #[arg(long)]
+    from: Option<Format>,
+
+    /// Output format, guessed from the output extension when omitted
+    #[arg(long)]
+    to: Option<Format>,
+
+    /// Field delimiter for CSV input and output
+    #[arg(long, default_value = ",", value_parser = csv::parse_delimiter)]
+    csv_delimiter: u8,
+
+    /// When to quote CSV output fields: necessary, always, non-numeric or never
+    #[arg(long, default_value = "necessary")]
+    csv_quoting: Quoting,
+
+    /// How sale dates are written: epoch, rfc3339 or a strftime pattern.
+    /// Dates are read from epoch seconds or ISO 8601 whatever the format
+    #[arg(long, default_value = "epoch")]
+    date_format: TimestampFormat,
+}
+
+fn main() {
+    let args = Args::parse();
+
+    if let Err(e) = run(args) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run(args: Args) -> Result<(), String> {
+    let input_path = get_input_file(args.input_path, "input-path")?;
+    let output_path = args.output_path.ok_or("output-path is required")?;
+
+    let from = match args.from {
+        Some(format) => format,
+        None => Format::from_path(&input_path)?,
+    };
+    let to = match args.to {
+        Some(format) => format,
+        None => Format::from_path(&output_path)?,
+    };
+
+    let csv_options = CsvOptions {
+        delimiter: args.csv_delimiter,
+        quoting: args.csv_quoting,
+        date_format: args.date_format.clone(),
+        ..Default::default()
+    };
+
+    let data = match from {
+        Format::Csv => csv::read_csv_dir(&input_path, &csv_options)?,
+        _ => from.read(&input_path)?,
+    };
+    match to {
+        Format::Csv => csv::write_csv_dir(&output_path, &data, &csv_options)?,
+        _ => to.write(&output_path, &data, &args.date_format)?,
+    }
+
+    println!(
+        "Converted {} products and {} sales from {} to {}",
+        data.products.len(),
+        data.sales.len(),
+        from,
+        to
+    );
+    Ok(())
+}
+
+fn get_input_file(
+    option: Option<std::path::PathBuf>,
+    arg_name: &str,
+) -> Result<std::path::PathBuf, String> {
+    match option {
+        Some(path) => {
+            if path.exists() {
+                Ok(path)
+            } else {
+                Err(format!("{} does not exist", arg_name))
+            }
+        }
+        None => Err(format!("{} is required", arg_name)),
+    }
+}
@@ -1,35 +1,176 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 
+mod ops;
+mod patch;
+mod selector;
+
+use ops::Operation;
+use patch::PatchKind;
+
+/// Applies JSONPath operations to a JSON document, e.g.
+/// `--exec "increment $.sales[?(@.unit=='Kg')].quantity 1.5"`, or JSON
+/// Patch and Merge Patch documents with the subcommands.
 #[derive(Parser, Debug)]
 struct Args {
-    #[arg(long)]
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[arg(long, global = true)]
     input_path: Option<std::path::PathBuf>,
 
-    #[arg(long)]
+    #[arg(long, global = true)]
     output_path: Option<std::path::PathBuf>,
+
+    /// Operation to apply: set, increment, multiply, delete or append, a
+    /// selector and a JSON value. Can be repeated
+    #[arg(long = "exec", short = 'e', value_name = "OPERATION")]
+    operations: Vec<String>,
+
+    /// File with one operation per line, applied before --exec. Blank lines
+    /// and lines starting with # are skipped
+    #[arg(long)]
+    script: Option<std::path::PathBuf>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Apply an RFC 6902 JSON Patch or RFC 7386 Merge Patch to the input
+    ApplyPatch {
+        #[arg(long)]
+        patch_path: Option<std::path::PathBuf>,
+
+        /// json-patch or merge-patch, guessed from the patch when omitted:
+        /// arrays are JSON Patches
+        #[arg(long)]
+        kind: Option<PatchKind>,
+    },
+    /// Write the patch turning the input into --target-path, to
+    /// --output-path or the standard output
+    Diff {
+        #[arg(long)]
+        target_path: Option<std::path::PathBuf>,
+
+        #[arg(long, default_value = "json-patch")]
+        kind: PatchKind,
+    },
 }
 
 fn main() {
     let args = Args::parse();
 
-    let input_path = get_input_file(args.input_path, "input-path")
-        .unwrap();
-    let output_path = get_input_file(args.output_path, "output-path")
-        .unwrap();
+    if let Err(e) = run(args) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run(args: Args) -> Result<(), String> {
+    match args.command {
+        Some(Command::ApplyPatch { patch_path, kind }) => {
+            apply_patch(args.input_path, args.output_path, patch_path, kind)
+        }
+        Some(Command::Diff { target_path, kind }) => diff(args.input_path, args.output_path, target_path, kind),
+        None => apply_operations(args.input_path, args.output_path, args.script, args.operations),
+    }
+}
+
+fn apply_operations(
+    input_path: Option<std::path::PathBuf>,
+    output_path: Option<std::path::PathBuf>,
+    script: Option<std::path::PathBuf>,
+    exec: Vec<String>,
+) -> Result<(), String> {
+    let input_path = get_input_file(input_path, "input-path")?;
+    let output_path = get_input_file(output_path, "output-path")?;
+
+    let mut operations = vec![];
+    if let Some(script) = script {
+        let script_path = get_input_file(Some(script), "script")?;
+        operations.extend(read_script(&script_path)?);
+    }
+    for operation in exec.iter() {
+        operations.push(operation.parse::<Operation>()?);
+    }
+    if operations.is_empty() {
+        return Err("no operations given, use --exec or --script".to_string());
+    }
+
+    let mut json = read_json(input_path)?;
+
+    for operation in operations.iter() {
+        let matched = operation.apply(&mut json).map_err(|e| format!("{}: {}", operation, e))?;
+        println!("{}: {} nodes matched", operation, matched);
+    }
+
+    write_json(&output_path, &json)
+}
+
+fn apply_patch(
+    input_path: Option<std::path::PathBuf>,
+    output_path: Option<std::path::PathBuf>,
+    patch_path: Option<std::path::PathBuf>,
+    kind: Option<PatchKind>,
+) -> Result<(), String> {
+    let input_path = get_input_file(input_path, "input-path")?;
+    let output_path = get_input_file(output_path, "output-path")?;
+    let patch_path = get_input_file(patch_path, "patch-path")?;
+
+    let mut json = read_json(input_path)?;
+    let patch = read_json(patch_path)?;
+    let kind = kind.unwrap_or_else(|| PatchKind::guess(&patch));
+
+    patch::apply(&mut json, &patch, kind)?;
+    match kind {
+        PatchKind::JsonPatch => println!("Applied {} operations", patch.as_array().map_or(0, |ops| ops.len())),
+        PatchKind::MergePatch => println!("Applied merge patch"),
+    }
+
+    write_json(&output_path, &json)
+}
+
+fn diff(
+    input_path: Option<std::path::PathBuf>,
+    output_path: Option<std::path::PathBuf>,
+    target_path: Option<std::path::PathBuf>,
+    kind: PatchKind,
+) -> Result<(), String> {
+    let input_path = get_input_file(input_path, "input-path")?;
+    let target_path = get_input_file(target_path, "target-path")?;
 
-    let mut json = read_json(input_path).unwrap();
+    let from = read_json(input_path)?;
+    let to = read_json(target_path)?;
+    let patch = patch::diff(&from, &to, kind)?;
 
-    if let serde_json::Value::Number(n) = &json["sales"][1]["quantity"] {
-        let new_value = n.as_f64().unwrap() + 1.5;
-        json["sales"][1]["quantity"] = serde_json::Value::Number(serde_json::Number::from_f64(new_value).unwrap());
-    } else {
-        panic!("sales doesn't have two values items or quantity is not a number");
+    match output_path {
+        Some(output_path) => write_json(&output_path, &patch),
+        None => {
+            println!("{}", serde_json::to_string_pretty(&patch).map_err(|e| e.to_string())?);
+            Ok(())
+        }
     }
+}
 
+fn write_json(output_path: &std::path::Path, json: &serde_json::Value) -> Result<(), String> {
     std::fs::write(
         output_path,
-        serde_json::to_string_pretty(&json).unwrap()
-    ).expect("Unable to write file");
+        serde_json::to_string_pretty(json).map_err(|e| e.to_string())?
+    ).map_err(|e| e.to_string())
+}
+
+fn read_script(script_path: &std::path::Path) -> Result<Vec<Operation>, String> {
+    let script = std::fs::read_to_string(script_path).map_err(|e| e.to_string())?;
+    let mut operations = vec![];
+    for (i, line) in script.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let operation = line
+            .parse()
+            .map_err(|e| format!("{}:{}: {}", script_path.display(), i + 1, e))?;
+        operations.push(operation);
+    }
+    Ok(operations)
 }
 
 fn get_input_file(
@@ -57,4 +198,4 @@ fn read_json(input_path: std::path::PathBuf) -> Result<serde_json::Value, String
     let reader = std::io::BufReader::new(file);
     let json: serde_json::Value = serde_json::from_reader(reader).map_err(|e| e.to_string())?;
     Ok(json)
-}
\ No newline at end of file
+}
@@ -0,0 +1,193 @@
+use crate::selector::{self, Selector, Step};
+use sales_model::{quantity, Decimal};
+use serde_json::{Number, Value};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+    /// Replaces the matched nodes, or adds the member to the matched objects
+    Set(Value),
+    Increment(Value),
+    Multiply(Value),
+    Delete,
+    /// Pushes a value onto the matched arrays
+    Append(Value),
+}
+
+impl Action {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Set(_) => "set",
+            Action::Increment(_) => "increment",
+            Action::Multiply(_) => "multiply",
+            Action::Delete => "delete",
+            Action::Append(_) => "append",
+        }
+    }
+}
+
+/// An action applied to every node matched by a selector, written as
+/// `<action> <selector> [<json value>]`, as in
+/// `increment $.sales[?(@.unit=='Kg')].quantity 1.5`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Operation {
+    pub action: Action,
+    pub selector: Selector,
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.action.name(), self.selector)?;
+        match &self.action {
+            Action::Set(value) | Action::Increment(value) | Action::Multiply(value) | Action::Append(value) => {
+                write!(f, " {}", value)
+            }
+            Action::Delete => Ok(()),
+        }
+    }
+}
+
+impl FromStr for Operation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (name, rest) = s
+            .split_once(char::is_whitespace)
+            .ok_or_else(|| format!("invalid operation `{}`, expected <action> <selector> [<value>]", s))?;
+        let (selector, value) = Selector::parse_prefix(rest.trim_start())?;
+        let value = value.trim();
+
+        let parse_value = || -> Result<Value, String> {
+            if value.is_empty() {
+                return Err(format!("`{}` needs a value", name));
+            }
+            serde_json::from_str(value).map_err(|e| format!("invalid JSON value `{}`: {}", value, e))
+        };
+        let action = match name {
+            "set" => Action::Set(parse_value()?),
+            "increment" => Action::Increment(parse_value()?),
+            "multiply" => Action::Multiply(parse_value()?),
+            "append" => Action::Append(parse_value()?),
+            "delete" if value.is_empty() => Action::Delete,
+            "delete" => return Err(format!("unexpected `{}` after selector `{}`", value, selector)),
+            _ => {
+                return Err(format!(
+                    "unknown action `{}`, expected one of: set, increment, multiply, delete, append",
+                    name
+                ))
+            }
+        };
+
+        Ok(Operation { action, selector })
+    }
+}
+
+impl Operation {
+    /// Applies the operation, returning how many nodes it matched.
+    pub fn apply(&self, json: &mut Value) -> Result<usize, String> {
+        if let (Action::Set(value), Some((parents, member))) = (&self.action, self.selector.split_member()) {
+            let mut count = 0;
+            for location in parents.select(json) {
+                if let Some(Value::Object(map)) = selector::get_mut(json, &location) {
+                    map.insert(member.to_string(), value.clone());
+                    count += 1;
+                }
+            }
+            return Ok(count);
+        }
+
+        let mut locations = self.selector.select(json);
+        if self.action == Action::Delete {
+            // Later siblings and descendants first, so the remaining
+            // locations stay valid
+            locations.sort();
+            locations.reverse();
+            locations.dedup();
+        }
+
+        for location in locations.iter() {
+            let at = || selector::pointer(location);
+            match &self.action {
+                Action::Delete => delete(json, location)?,
+                action => {
+                    let node = selector::get_mut(json, location).ok_or_else(|| format!("{} not found", at()))?;
+                    match action {
+                        Action::Set(value) => *node = value.clone(),
+                        Action::Increment(operand) => {
+                            *node = arithmetic(node, operand, i64::checked_add, Decimal::checked_add, |a, b| a + b)
+                                .map_err(|e| format!("{}: {}", at(), e))?
+                        }
+                        Action::Multiply(operand) => {
+                            *node = arithmetic(node, operand, i64::checked_mul, Decimal::checked_mul, |a, b| a * b)
+                                .map_err(|e| format!("{}: {}", at(), e))?
+                        }
+                        Action::Append(value) => match node {
+                            Value::Array(array) => array.push(value.clone()),
+                            other => return Err(format!("{}: cannot append to {}", at(), type_name(other))),
+                        },
+                        Action::Delete => unreachable!(),
+                    }
+                }
+            }
+        }
+
+        Ok(locations.len())
+    }
+}
+
+fn delete(json: &mut Value, location: &[Step]) -> Result<(), String> {
+    let (last, parent) = location.split_last().ok_or("cannot delete the whole document")?;
+    match (selector::get_mut(json, parent), last) {
+        (Some(Value::Object(map)), Step::Key(key)) => {
+            map.remove(key);
+        }
+        (Some(Value::Array(array)), Step::Index(index)) if *index < array.len() => {
+            array.remove(*index);
+        }
+        _ => return Err(format!("{} not found", selector::pointer(location))),
+    }
+    Ok(())
+}
+
+// Integers stay integers unless the result overflows. Other numbers are
+// taken as the decimals they are written as, so 2.14 + 1.5 is 3.64, and
+// only fall back to float arithmetic beyond the range of a decimal
+fn arithmetic(
+    current: &Value,
+    operand: &Value,
+    int_op: fn(i64, i64) -> Option<i64>,
+    decimal_op: fn(Decimal, Decimal) -> Option<Decimal>,
+    float_op: fn(f64, f64) -> f64,
+) -> Result<Value, String> {
+    let (a, b) = match (current, operand) {
+        (Value::Number(a), Value::Number(b)) => (a, b),
+        (Value::Number(_), other) => return Err(format!("operand must be a number, found {}", type_name(other))),
+        (other, _) => return Err(format!("expected a number, found {}", type_name(other))),
+    };
+
+    if let Some(result) = a.as_i64().zip(b.as_i64()).and_then(|(a, b)| int_op(a, b)) {
+        return Ok(Value::from(result));
+    }
+    let (a, b) = (a.as_f64().unwrap_or(f64::NAN), b.as_f64().unwrap_or(f64::NAN));
+    let decimal = quantity::from_f64(a)
+        .zip(quantity::from_f64(b))
+        .and_then(|(a, b)| decimal_op(a, b))
+        .and_then(|result| result.to_string().parse::<f64>().ok());
+    let result = decimal.unwrap_or_else(|| float_op(a, b));
+    Number::from_f64(result)
+        .map(Value::Number)
+        .ok_or_else(|| format!("result {} is not a valid JSON number", result))
+}
+
+pub fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
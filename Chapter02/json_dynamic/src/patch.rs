@@ -0,0 +1,163 @@
+use crate::selector::{self, Location, Step};
+use serde_json::{Map, Value};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PatchKind {
+    /// RFC 6902, an array of operations
+    JsonPatch,
+    /// RFC 7386, a partial document where `null` removes members
+    MergePatch,
+}
+
+impl PatchKind {
+    /// JSON Patches are arrays, anything else is taken as a Merge Patch.
+    pub fn guess(patch: &Value) -> PatchKind {
+        if patch.is_array() {
+            PatchKind::JsonPatch
+        } else {
+            PatchKind::MergePatch
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PatchKind::JsonPatch => "json-patch",
+            PatchKind::MergePatch => "merge-patch",
+        }
+    }
+}
+
+impl fmt::Display for PatchKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for PatchKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "json-patch" | "json" | "6902" => Ok(PatchKind::JsonPatch),
+            "merge-patch" | "merge" | "7386" => Ok(PatchKind::MergePatch),
+            _ => Err(format!("unknown patch kind `{}`, expected json-patch or merge-patch", s)),
+        }
+    }
+}
+
+/// Applies `patch` to `json`. A JSON Patch is applied atomically: when an
+/// operation fails, `json` is left unchanged.
+pub fn apply(json: &mut Value, patch: &Value, kind: PatchKind) -> Result<(), String> {
+    match kind {
+        PatchKind::JsonPatch => {
+            let patch: json_patch::Patch =
+                serde_json::from_value(patch.clone()).map_err(|e| format!("invalid JSON Patch: {}", e))?;
+            json_patch::patch(json, &patch.0).map_err(|e| e.to_string())
+        }
+        PatchKind::MergePatch => {
+            json_patch::merge(json, patch);
+            Ok(())
+        }
+    }
+}
+
+/// A patch turning `from` into `to`.
+pub fn diff(from: &Value, to: &Value, kind: PatchKind) -> Result<Value, String> {
+    match kind {
+        PatchKind::JsonPatch => serde_json::to_value(json_patch::diff(from, to)).map_err(|e| e.to_string()),
+        PatchKind::MergePatch => merge_diff(from, to, &mut vec![]),
+    }
+}
+
+// Merge Patches replace arrays whole and cannot set a member to null, as
+// null means removal
+fn merge_diff(from: &Value, to: &Value, location: &mut Location) -> Result<Value, String> {
+    match (from, to) {
+        (Value::Object(from), Value::Object(to)) => {
+            let mut patch = Map::new();
+            for (key, from_value) in from.iter() {
+                location.push(Step::Key(key.clone()));
+                match to.get(key) {
+                    None => {
+                        patch.insert(key.clone(), Value::Null);
+                    }
+                    Some(to_value) if to_value != from_value => {
+                        patch.insert(key.clone(), merge_diff(from_value, to_value, location)?);
+                    }
+                    Some(_) => {}
+                }
+                location.pop();
+            }
+            for (key, to_value) in to.iter() {
+                if !from.contains_key(key) {
+                    location.push(Step::Key(key.clone()));
+                    check_no_null_members(to_value, location)?;
+                    location.pop();
+                    patch.insert(key.clone(), to_value.clone());
+                }
+            }
+            Ok(Value::Object(patch))
+        }
+        _ => {
+            check_no_null_members(to, location)?;
+            Ok(to.clone())
+        }
+    }
+}
+
+// A whole document set to null is fine, but a null member would be removed
+fn check_no_null_members(value: &Value, location: &mut Location) -> Result<(), String> {
+    match value {
+        Value::Null if !location.is_empty() => Err(format!(
+            "{} is null in the target, which a merge patch cannot express; use --kind json-patch",
+            selector::pointer(location)
+        )),
+        Value::Object(map) => {
+            for (key, value) in map.iter() {
+                location.push(Step::Key(key.clone()));
+                check_no_null_members(value, location)?;
+                location.pop();
+            }
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn merge_diffs_apply_back_to_the_target() {
+        let from = json!({"a": 1, "b": {"c": 2, "d": 3}, "e": [1, 2]});
+        let to = json!({"b": {"c": 2, "d": 4, "f": {"g": 5}}, "e": [null], "h": "x"});
+        let patch = diff(&from, &to, PatchKind::MergePatch).unwrap();
+        assert_eq!(patch, json!({"a": null, "b": {"d": 4, "f": {"g": 5}}, "e": [null], "h": "x"}));
+
+        let mut json = from.clone();
+        apply(&mut json, &patch, PatchKind::MergePatch).unwrap();
+        assert_eq!(json, to);
+        assert_eq!(diff(&from, &json!(null), PatchKind::MergePatch).unwrap(), json!(null));
+    }
+
+    #[test]
+    fn merge_diffs_refuse_null_members() {
+        let from = json!({"a": 1, "b": {}});
+        for (to, at) in [
+            (json!({"a": null, "b": {}}), "/a"),
+            (json!({"a": 1, "b": {"c": null}}), "/b/c"),
+            (json!({"a": 1, "b": {}, "d": {"e": {"f/g": null}}}), "/d/e/f~1g"),
+            (json!({"a": {"c": null}, "b": {}}), "/a/c"),
+        ] {
+            assert_eq!(
+                diff(&from, &to, PatchKind::MergePatch).unwrap_err(),
+                format!("{} is null in the target, which a merge patch cannot express; use --kind json-patch", at)
+            );
+            assert!(diff(&from, &to, PatchKind::JsonPatch).is_ok());
+        }
+    }
+}
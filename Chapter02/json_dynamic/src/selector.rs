@@ -0,0 +1,680 @@
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::fmt;
+
+/// One step of a concrete location in a document.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Step {
+    Key(String),
+    Index(usize),
+}
+
+pub type Location = Vec<Step>;
+
+/// The RFC 6901 JSON Pointer of a location, as in `/sales/1/quantity`.
+pub fn pointer(location: &[Step]) -> String {
+    let mut pointer = String::new();
+    for step in location.iter() {
+        pointer.push('/');
+        match step {
+            Step::Key(key) => pointer.push_str(&key.replace('~', "~0").replace('/', "~1")),
+            Step::Index(index) => pointer.push_str(&index.to_string()),
+        }
+    }
+    pointer
+}
+
+pub fn get_mut<'a>(json: &'a mut Value, location: &[Step]) -> Option<&'a mut Value> {
+    let mut node = json;
+    for step in location.iter() {
+        node = match step {
+            Step::Key(key) => node.as_object_mut()?.get_mut(key)?,
+            Step::Index(index) => node.as_array_mut()?.get_mut(*index)?,
+        };
+    }
+    Some(node)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Segment {
+    Child(String),
+    /// An array index, counted from the end when negative
+    Index(i64),
+    /// `[start:end:step]`, with each bound optional as in RFC 9535
+    Slice(Option<i64>, Option<i64>, Option<i64>),
+    Wildcard,
+    /// `..` followed by a segment, applied to the node and all its descendants
+    Descendants(Box<Segment>),
+    Filter(Filter),
+}
+
+/// Conditions joined by `||` of conditions joined by `&&`.
+#[derive(Clone, Debug, PartialEq)]
+struct Filter(Vec<Vec<Condition>>);
+
+/// `@.path`, alone to test that it exists, or compared to a literal.
+#[derive(Clone, Debug, PartialEq)]
+struct Condition {
+    path: Vec<Step>,
+    comparison: Option<(Comparison, Value)>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// A JSONPath selector such as `$.sales[?(@.unit=='Kg')].quantity`.
+///
+/// Supported: `$`, `.name`, `['name']`, `[0]`, `[-1]`, slices such as
+/// `[1:3]`, `[-2:]` and `[::2]`, `.*`, `[*]`, `..name` and filters comparing
+/// `@` paths to literals with `==`, `!=`, `<`, `<=`, `>`, `>=`, joined by
+/// `&&` and `||`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Selector {
+    text: String,
+    segments: Vec<Segment>,
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+impl Selector {
+    /// Parses a selector at the start of `input`, returning it with the
+    /// text that follows it.
+    pub fn parse_prefix(input: &str) -> Result<(Selector, &str), String> {
+        let mut parser = Parser {
+            input,
+            pos: 0,
+        };
+        let segments = parser.selector()?;
+        let text = input[..parser.pos].to_string();
+        Ok((Selector { text, segments }, &input[parser.pos..]))
+    }
+
+    /// Splits off the last segment when it names an object member, so the
+    /// member can be created in the objects the rest of the selector matches.
+    pub fn split_member(&self) -> Option<(Selector, &str)> {
+        match self.segments.split_last() {
+            Some((Segment::Child(name), parents)) => {
+                let parent = Selector {
+                    text: self.text.clone(),
+                    segments: parents.to_vec(),
+                };
+                Some((parent, name.as_str()))
+            }
+            _ => None,
+        }
+    }
+
+    /// Locations of the matched nodes, in document order, or reversed by a
+    /// slice with a negative step.
+    pub fn select(&self, json: &Value) -> Vec<Location> {
+        let mut matches = vec![(vec![], json)];
+        for segment in self.segments.iter() {
+            let mut next = vec![];
+            for (location, node) in matches {
+                apply(segment, location, node, &mut next);
+            }
+            matches = next;
+        }
+        matches.into_iter().map(|(location, _)| location).collect()
+    }
+}
+
+fn apply<'a>(segment: &Segment, location: Location, node: &'a Value, out: &mut Vec<(Location, &'a Value)>) {
+    let child = |step: Step| {
+        let mut location = location.clone();
+        location.push(step);
+        location
+    };
+
+    match segment {
+        Segment::Child(name) => {
+            if let Some(value) = node.get(name) {
+                out.push((child(Step::Key(name.clone())), value));
+            }
+        }
+        Segment::Index(index) => {
+            if let Some(array) = node.as_array() {
+                let index = if *index < 0 { array.len() as i64 + index } else { *index };
+                if let Ok(index) = usize::try_from(index) {
+                    if let Some(value) = array.get(index) {
+                        out.push((child(Step::Index(index)), value));
+                    }
+                }
+            }
+        }
+        Segment::Slice(start, end, step) => {
+            if let Some(array) = node.as_array() {
+                for index in slice_indices(array.len() as i64, *start, *end, step.unwrap_or(1)) {
+                    out.push((child(Step::Index(index)), &array[index]));
+                }
+            }
+        }
+        Segment::Wildcard | Segment::Filter(_) => {
+            let children: Vec<(Step, &Value)> = match node {
+                Value::Object(map) => map.iter().map(|(k, v)| (Step::Key(k.clone()), v)).collect(),
+                Value::Array(array) => array.iter().enumerate().map(|(i, v)| (Step::Index(i), v)).collect(),
+                _ => vec![],
+            };
+            for (step, value) in children {
+                let keep = match segment {
+                    Segment::Filter(filter) => filter.matches(value),
+                    _ => true,
+                };
+                if keep {
+                    out.push((child(step), value));
+                }
+            }
+        }
+        Segment::Descendants(inner) => {
+            apply(inner, location.clone(), node, out);
+            match node {
+                Value::Object(map) => {
+                    for (key, value) in map.iter() {
+                        apply(segment, child(Step::Key(key.clone())), value, out);
+                    }
+                }
+                Value::Array(array) => {
+                    for (i, value) in array.iter().enumerate() {
+                        apply(segment, child(Step::Index(i)), value, out);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// The indices a slice selects from an array of `len` elements, as in
+/// Python; a step of zero selects nothing.
+fn slice_indices(len: i64, start: Option<i64>, end: Option<i64>, step: i64) -> Vec<usize> {
+    let normalize = |i: i64| if i < 0 { len + i } else { i };
+    let mut indices = vec![];
+    if step > 0 {
+        let mut i = normalize(start.unwrap_or(0)).clamp(0, len);
+        let upper = normalize(end.unwrap_or(len)).clamp(0, len);
+        while i < upper {
+            indices.push(i as usize);
+            i += step;
+        }
+    } else if step < 0 {
+        let mut i = start.map_or(len - 1, normalize).clamp(-1, len - 1);
+        let lower = end.map_or(-1, normalize).clamp(-1, len - 1);
+        while i > lower {
+            indices.push(i as usize);
+            i += step;
+        }
+    }
+    indices
+}
+
+impl Filter {
+    fn matches(&self, node: &Value) -> bool {
+        self.0
+            .iter()
+            .any(|conditions| conditions.iter().all(|condition| condition.matches(node)))
+    }
+}
+
+impl Condition {
+    fn matches(&self, node: &Value) -> bool {
+        let mut value = node;
+        for step in self.path.iter() {
+            let next = match step {
+                Step::Key(key) => value.get(key),
+                Step::Index(index) => value.get(index),
+            };
+            match next {
+                Some(next) => value = next,
+                None => return false,
+            }
+        }
+
+        let (comparison, literal) = match &self.comparison {
+            Some(comparison) => comparison,
+            None => return true,
+        };
+        let ordering = match (value, literal) {
+            (Value::Number(a), Value::Number(b)) => a.as_f64().partial_cmp(&b.as_f64()),
+            (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+            (a, b) if a == b => Some(Ordering::Equal),
+            _ => None,
+        };
+        match comparison {
+            Comparison::Eq => ordering == Some(Ordering::Equal),
+            Comparison::Ne => ordering != Some(Ordering::Equal),
+            Comparison::Lt => ordering == Some(Ordering::Less),
+            Comparison::Le => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+            Comparison::Gt => ordering == Some(Ordering::Greater),
+            Comparison::Ge => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+        }
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn eat(&mut self, s: &str) -> bool {
+        if self.rest().starts_with(s) {
+            self.pos += s.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, s: &str) -> Result<(), String> {
+        if self.eat(s) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected `{}`", s)))
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        let trimmed = self.rest().trim_start();
+        self.pos = self.input.len() - trimmed.len();
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!("invalid selector `{}`: {} at column {}", self.input, message, self.pos + 1)
+    }
+
+    fn selector(&mut self) -> Result<Vec<Segment>, String> {
+        self.expect("$")?;
+        let mut segments = vec![];
+        loop {
+            if self.eat("..") {
+                let inner = match self.peek() {
+                    Some('[') => self.bracket()?,
+                    _ => self.dot_member()?,
+                };
+                segments.push(Segment::Descendants(Box::new(inner)));
+            } else if self.eat(".") {
+                segments.push(self.dot_member()?);
+            } else if self.peek() == Some('[') {
+                segments.push(self.bracket()?);
+            } else {
+                return Ok(segments);
+            }
+        }
+    }
+
+    fn dot_member(&mut self) -> Result<Segment, String> {
+        if self.eat("*") {
+            return Ok(Segment::Wildcard);
+        }
+        let name = self.name();
+        if name.is_empty() {
+            return Err(self.error("expected a member name"));
+        }
+        Ok(Segment::Child(name.to_string()))
+    }
+
+    fn name(&mut self) -> &'a str {
+        let rest = self.rest();
+        let end = rest
+            .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-'))
+            .unwrap_or(rest.len());
+        self.pos += end;
+        &rest[..end]
+    }
+
+    fn bracket(&mut self) -> Result<Segment, String> {
+        self.expect("[")?;
+        self.skip_whitespace();
+        let segment = if self.eat("*") {
+            Segment::Wildcard
+        } else if self.eat("?") {
+            self.skip_whitespace();
+            self.expect("(")?;
+            let filter = self.filter()?;
+            self.skip_whitespace();
+            self.expect(")")?;
+            Segment::Filter(filter)
+        } else if matches!(self.peek(), Some('\'' | '"')) {
+            Segment::Child(self.quoted()?)
+        } else {
+            let start = self.integer()?;
+            if self.eat(":") {
+                let end = self.integer()?;
+                let step = if self.eat(":") { self.integer()? } else { None };
+                Segment::Slice(start, end, step)
+            } else {
+                Segment::Index(start.ok_or_else(|| self.error("expected an index"))?)
+            }
+        };
+        self.skip_whitespace();
+        self.expect("]")?;
+        Ok(segment)
+    }
+
+    fn integer(&mut self) -> Result<Option<i64>, String> {
+        self.skip_whitespace();
+        let rest = self.rest();
+        let end = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '-'))
+            .unwrap_or(rest.len());
+        if end == 0 {
+            return Ok(None);
+        }
+        let integer = rest[..end].parse().map_err(|_| self.error("expected an integer"))?;
+        self.pos += end;
+        self.skip_whitespace();
+        Ok(Some(integer))
+    }
+
+    fn quoted(&mut self) -> Result<String, String> {
+        let quote = self.peek().ok_or_else(|| self.error("expected a quote"))?;
+        self.pos += 1;
+        let mut s = String::new();
+        let mut chars = self.rest().char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some((_, escaped)) => s.push(escaped),
+                    None => break,
+                },
+                c if c == quote => {
+                    self.pos += i + 1;
+                    return Ok(s);
+                }
+                c => s.push(c),
+            }
+        }
+        Err(self.error("unterminated string"))
+    }
+
+    fn filter(&mut self) -> Result<Filter, String> {
+        let mut any = vec![];
+        loop {
+            let mut all = vec![];
+            loop {
+                all.push(self.condition()?);
+                self.skip_whitespace();
+                if !self.eat("&&") {
+                    break;
+                }
+            }
+            any.push(all);
+            if !self.eat("||") {
+                return Ok(Filter(any));
+            }
+        }
+    }
+
+    fn condition(&mut self) -> Result<Condition, String> {
+        self.skip_whitespace();
+        self.expect("@")?;
+        let mut path = vec![];
+        loop {
+            if self.eat(".") {
+                let name = self.name();
+                if name.is_empty() {
+                    return Err(self.error("expected a member name"));
+                }
+                path.push(Step::Key(name.to_string()));
+            } else if self.peek() == Some('[') {
+                match self.bracket()? {
+                    Segment::Child(name) => path.push(Step::Key(name)),
+                    Segment::Index(index) if index >= 0 => path.push(Step::Index(index as usize)),
+                    _ => return Err(self.error("only names and indices are allowed in filters")),
+                }
+            } else {
+                break;
+            }
+        }
+
+        self.skip_whitespace();
+        let comparisons = [
+            ("==", Comparison::Eq),
+            ("!=", Comparison::Ne),
+            ("<=", Comparison::Le),
+            (">=", Comparison::Ge),
+            ("<", Comparison::Lt),
+            (">", Comparison::Gt),
+        ];
+        let comparison = comparisons.iter().find(|(op, _)| self.eat(op)).map(|(_, c)| *c);
+        let comparison = match comparison {
+            Some(comparison) => {
+                self.skip_whitespace();
+                Some((comparison, self.literal()?))
+            }
+            None => None,
+        };
+        Ok(Condition { path, comparison })
+    }
+
+    fn literal(&mut self) -> Result<Value, String> {
+        if self.peek() == Some('\'') {
+            return self.quoted().map(Value::String);
+        }
+        let rest = self.rest();
+        let end = if rest.starts_with('"') {
+            // Let serde_json handle the escapes of double quoted strings
+            let mut stream = serde_json::Deserializer::from_str(rest).into_iter::<Value>();
+            stream.next();
+            stream.byte_offset()
+        } else {
+            rest.find(|c: char| c.is_whitespace() || matches!(c, ')' | '&' | '|'))
+                .unwrap_or(rest.len())
+        };
+        let value = serde_json::from_str(&rest[..end]).map_err(|_| self.error("expected a literal"))?;
+        self.pos += end;
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn document() -> Value {
+        json!({
+            "products": [
+                {"id": 190, "category": "furniture", "name": "chair"},
+                {"id": 591, "category": "fruit", "name": "orange"}
+            ],
+            "sales": [
+                {"id": "a", "product_id": 190, "quantity": 4, "unit": "pc"},
+                {"id": "b", "product_id": 591, "quantity": 2.14, "unit": "Kg"},
+                {"id": "c", "product_id": 591, "quantity": 0.5, "unit": "Kg"},
+                {"id": "d", "product_id": 190, "quantity": 1, "unit": "pc", "note": null}
+            ]
+        })
+    }
+
+    // The pointers of the locations `selector` matches in the document
+    fn select(selector: &str) -> Vec<String> {
+        let (selector, rest) = Selector::parse_prefix(selector).unwrap();
+        assert_eq!(rest, "");
+        selector.select(&document()).iter().map(|location| pointer(location)).collect()
+    }
+
+    fn parse_error(selector: &str) -> String {
+        Selector::parse_prefix(selector).unwrap_err()
+    }
+
+    #[test]
+    fn members_and_indices() {
+        assert_eq!(select("$"), [""]);
+        assert_eq!(select("$.sales[1].quantity"), ["/sales/1/quantity"]);
+        assert_eq!(select("$['sales'][0][\"id\"]"), ["/sales/0/id"]);
+        assert_eq!(select("$.sales[-1].id"), ["/sales/3/id"]);
+        assert_eq!(select("$.sales[ 2 ]"), ["/sales/2"]);
+        assert!(select("$.sales[4]").is_empty());
+        assert!(select("$.sales[-5]").is_empty());
+        assert!(select("$.missing.id").is_empty());
+        assert!(select("$.sales.id").is_empty());
+    }
+
+    #[test]
+    fn quoted_names_take_escapes_and_any_character() {
+        let json = json!({"a b": 1, "it's": 2, "x/y~z": 3});
+        let select = |selector: &str| {
+            let (selector, _) = Selector::parse_prefix(selector).unwrap();
+            selector.select(&json).iter().map(|location| pointer(location)).collect::<Vec<_>>()
+        };
+        assert_eq!(select("$['a b']"), ["/a b"]);
+        assert_eq!(select("$['it\\'s']"), ["/it's"]);
+        assert_eq!(select("$[\"x/y~z\"]"), ["/x~1y~0z"]);
+    }
+
+    #[test]
+    fn wildcards_select_every_child_in_order() {
+        assert_eq!(select("$.products.*.name"), ["/products/0/name", "/products/1/name"]);
+        assert_eq!(select("$.products[*].id"), ["/products/0/id", "/products/1/id"]);
+        assert_eq!(select("$.products[0].*").len(), 3);
+        assert!(select("$.sales[0].id.*").is_empty());
+    }
+
+    #[test]
+    fn slices() {
+        assert_eq!(select("$.sales[1:3].id"), ["/sales/1/id", "/sales/2/id"]);
+        assert_eq!(select("$.sales[:2].id"), ["/sales/0/id", "/sales/1/id"]);
+        assert_eq!(select("$.sales[2:].id"), ["/sales/2/id", "/sales/3/id"]);
+        assert_eq!(select("$.sales[:].id").len(), 4);
+        assert_eq!(select("$.sales[::2].id"), ["/sales/0/id", "/sales/2/id"]);
+        assert_eq!(select("$.sales[ 0 : 4 : 3 ].id"), ["/sales/0/id", "/sales/3/id"]);
+    }
+
+    #[test]
+    fn negative_slices_count_from_the_end() {
+        assert_eq!(select("$.sales[-2:].id"), ["/sales/2/id", "/sales/3/id"]);
+        assert_eq!(select("$.sales[:-3].id"), ["/sales/0/id"]);
+        assert_eq!(select("$.sales[-10:1].id"), ["/sales/0/id"]);
+        assert_eq!(select("$.sales[::-1].id"), ["/sales/3/id", "/sales/2/id", "/sales/1/id", "/sales/0/id"]);
+        assert_eq!(select("$.sales[2:0:-1].id"), ["/sales/2/id", "/sales/1/id"]);
+    }
+
+    #[test]
+    fn empty_slices_select_nothing() {
+        assert!(select("$.sales[2:2]").is_empty());
+        assert!(select("$.sales[3:1]").is_empty());
+        assert!(select("$.sales[10:]").is_empty());
+        assert!(select("$.sales[:-10]").is_empty());
+        assert!(select("$.sales[::0]").is_empty());
+        assert!(select("$.sales[0:2:-1]").is_empty());
+        assert!(select("$.products[0][0:1]").is_empty());
+        assert!(select("$.missing[:]").is_empty());
+    }
+
+    #[test]
+    fn recursive_descent_visits_the_node_and_its_descendants() {
+        assert_eq!(select("$..name"), ["/products/0/name", "/products/1/name"]);
+        assert_eq!(select("$..['name']"), ["/products/0/name", "/products/1/name"]);
+        assert_eq!(select("$.sales[0]..id"), ["/sales/0/id"]);
+        assert_eq!(select("$..[0].id"), ["/products/0/id", "/sales/0/id"]);
+        assert_eq!(select("$..[?(@.unit=='Kg')].id"), ["/sales/1/id", "/sales/2/id"]);
+        assert!(select("$..missing").is_empty());
+
+        let all = select("$..*");
+        // The two arrays, their elements and the members of each
+        assert_eq!(all.len(), 2 + 2 + 2 * 3 + 4 + 4 * 4 + 1);
+        assert!(all.contains(&"/sales/3/note".to_string()));
+    }
+
+    #[test]
+    fn filters_compare_paths_to_literals() {
+        assert_eq!(select("$.sales[?(@.unit=='Kg')].id"), ["/sales/1/id", "/sales/2/id"]);
+        assert_eq!(select("$.sales[?(@.unit == \"pc\")].id"), ["/sales/0/id", "/sales/3/id"]);
+        assert_eq!(select("$.sales[?(@.unit != 'Kg')].id"), ["/sales/0/id", "/sales/3/id"]);
+        assert_eq!(select("$.sales[?(@.quantity < 1)].id"), ["/sales/2/id"]);
+        assert_eq!(select("$.sales[?(@.quantity <= 1)].id"), ["/sales/2/id", "/sales/3/id"]);
+        assert_eq!(select("$.sales[?(@.quantity > 2.14)].id"), ["/sales/0/id"]);
+        assert_eq!(select("$.sales[?(@.quantity >= 2.14)].id"), ["/sales/0/id", "/sales/1/id"]);
+        assert_eq!(select("$.sales[?(@.id > 'b')].id"), ["/sales/2/id", "/sales/3/id"]);
+        assert_eq!(select("$.sales[?(@['product_id'] == 591)].id"), ["/sales/1/id", "/sales/2/id"]);
+        assert_eq!(select("$[?(@[0].id == 190)]"), ["/products"]);
+    }
+
+    #[test]
+    fn filters_test_existence_and_join_conditions() {
+        assert_eq!(select("$.sales[?(@.note)].id"), ["/sales/3/id"]);
+        assert_eq!(select("$.sales[?(@.note == null)].id"), ["/sales/3/id"]);
+        assert_eq!(select("$.sales[?(@.unit == 'Kg' && @.quantity < 1)].id"), ["/sales/2/id"]);
+        assert_eq!(select("$.sales[?(@.id == 'a' || @.id == 'd')].id"), ["/sales/0/id", "/sales/3/id"]);
+        assert_eq!(
+            select("$.sales[?(@.unit == 'pc' && @.quantity > 1 || @.quantity < 1)].id"),
+            ["/sales/0/id", "/sales/2/id"]
+        );
+        // Values of different types are never ordered, and only unequal
+        assert!(select("$.sales[?(@.quantity > '1')]").is_empty());
+        assert_eq!(select("$.sales[?(@.quantity != '1')]").len(), 4);
+        assert!(select("$.sales[?(@.missing == 1)]").is_empty());
+    }
+
+    #[test]
+    fn parse_prefix_returns_the_text_after_the_selector() {
+        let (selector, rest) = Selector::parse_prefix("$.sales[?(@.id == 'a b')].quantity 1.5").unwrap();
+        assert_eq!(selector.to_string(), "$.sales[?(@.id == 'a b')].quantity");
+        assert_eq!(rest, " 1.5");
+    }
+
+    #[test]
+    fn split_member_only_splits_a_trailing_name() {
+        let (selector, _) = Selector::parse_prefix("$.sales[*].note").unwrap();
+        let (parents, member) = selector.split_member().unwrap();
+        assert_eq!(member, "note");
+        assert_eq!(parents.select(&document()).len(), 4);
+
+        for text in ["$", "$.sales[0]", "$.sales[1:]", "$.sales.*", "$..note"] {
+            let (selector, _) = Selector::parse_prefix(text).unwrap();
+            assert!(selector.split_member().is_none(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn errors_give_the_column() {
+        assert_eq!(parse_error("sales"), "invalid selector `sales`: expected `$` at column 1");
+        assert_eq!(parse_error("$."), "invalid selector `$.`: expected a member name at column 3");
+        assert_eq!(parse_error("$.."), "invalid selector `$..`: expected a member name at column 4");
+        assert_eq!(parse_error("$.sales[1"), "invalid selector `$.sales[1`: expected `]` at column 10");
+        assert_eq!(parse_error("$.sales[x]"), "invalid selector `$.sales[x]`: expected an index at column 9");
+        assert_eq!(parse_error("$.sales[1-]"), "invalid selector `$.sales[1-]`: expected an integer at column 9");
+        assert_eq!(parse_error("$.sales[1:2:3:4]"), "invalid selector `$.sales[1:2:3:4]`: expected `]` at column 14");
+        assert_eq!(parse_error("$['sales]"), "invalid selector `$['sales]`: unterminated string at column 4");
+        assert_eq!(
+            parse_error("$.sales[?(@.id == 'a']"),
+            "invalid selector `$.sales[?(@.id == 'a']`: expected `)` at column 22"
+        );
+        assert_eq!(
+            parse_error("$.sales[?(id == 'a')]"),
+            "invalid selector `$.sales[?(id == 'a')]`: expected `@` at column 11"
+        );
+        assert_eq!(
+            parse_error("$.sales[?(@.id == a)]"),
+            "invalid selector `$.sales[?(@.id == a)]`: expected a literal at column 19"
+        );
+        assert_eq!(
+            parse_error("$.sales[?(@[*] == 1)]"),
+            "invalid selector `$.sales[?(@[*] == 1)]`: only names and indices are allowed in filters at column 15"
+        );
+        assert_eq!(
+            parse_error("$.sales[?(@[-1] == 1)]"),
+            "invalid selector `$.sales[?(@[-1] == 1)]`: only names and indices are allowed in filters at column 16"
+        );
+    }
+}
@@ -1,6 +1,10 @@
 use clap::Parser;
-use serde::{Deserialize, Serialize};
+use sales_model::{jsonl, read_json, write_json, Decimal, Format, SalesAndProducts, TimestampFormat};
+use std::path::Path;
 
+/// Paths ending in `.jsonl` or `.ndjson` are JSON Lines files of tagged
+/// records, and directories, or paths ending in a separator such as `out/`,
+/// hold `products.jsonl` and `sales.jsonl`.
 #[derive(Parser, Debug)]
 struct Args {
     #[arg(long)]
@@ -8,28 +12,15 @@ struct Args {
 
     #[arg(long)]
     output_path: Option<std::path::PathBuf>,
-}
-
-#[derive(Deserialize, Serialize, Debug)]
-struct SalesAndProducts {
-    products: Vec<Product>,
-    sales: Vec<Sale>,
-}
 
-#[derive(Deserialize, Serialize, Debug)]
-struct Product {
-    id: u32,
-    category: String,
-    name: String,
-}
+    /// Add the records to the end of a JSON Lines output instead of
+    /// replacing it
+    #[arg(long)]
+    append: bool,
 
-#[derive(Deserialize, Serialize, Debug)]
-struct Sale {
-    id: String,
-    product_id: u32,
-    date: u64,
-    quantity: f64,
-    unit: String,
+    /// How sale dates are written: epoch, rfc3339 or a strftime pattern
+    #[arg(long, default_value = "epoch")]
+    date_format: TimestampFormat,
 }
 
 fn main() {
@@ -39,14 +30,30 @@ fn main() {
         .unwrap();
     let output_path = get_input_file(args.output_path, "output-path")
         .unwrap();
+    if args.append && !is_json_lines(&output_path) {
+        eprintln!("--append needs a JSON Lines output");
+        std::process::exit(1);
+    }
+
+    let mut json: SalesAndProducts = if is_json_lines(&input_path) {
+        jsonl::read(input_path).unwrap()
+    } else {
+        read_json(input_path).unwrap()
+    };
+    json.sales[1].quantity += Decimal::new(15, 1);
 
-    let mut json = read_json(input_path).unwrap();
-    json.sales[1].quantity += 1.5;
+    let written = if !is_json_lines(&output_path) {
+        write_json(output_path, &json, &args.date_format)
+    } else if args.append {
+        jsonl::append(output_path, &json, &args.date_format)
+    } else {
+        jsonl::write(output_path, &json, &args.date_format)
+    };
+    written.expect("Unable to write file");
+}
 
-    std::fs::write(
-        output_path,
-        serde_json::to_string_pretty(&json).unwrap()
-    ).expect("Unable to write file");
+fn is_json_lines(path: &Path) -> bool {
+    jsonl::is_dir_path(path) || Format::from_path(path) == Ok(Format::JsonLines)
 }
 
 fn get_input_file(
@@ -68,10 +75,3 @@ fn get_input_file(
         None => Err(format!("{} is required", arg_name)),
     }
 }
-
-fn read_json(input_path: std::path::PathBuf) -> Result<SalesAndProducts, String> {
-    let file = std::fs::File::open(input_path).map_err(|e| e.to_string())?;
-    let reader = std::io::BufReader::new(file);
-    let json: SalesAndProducts = serde_json::from_reader(reader).map_err(|e| e.to_string())?;
-    Ok(json)
-}
\ No newline at end of file
@@ -0,0 +1,122 @@
+use clap::Parser;
+use sales_model::config;
+use sales_model::store::{self, BulkLoad, PostgresStore, SalesStore};
+use sales_model::{Decimal, LoadMode, Product, Sale, Timestamp, Unit};
+use std::time::{Duration, Instant};
+
+/// Times loading a generated dataset into Postgres with each bulk load
+/// method. Every run happens in a transaction that is rolled back, so the
+/// database is left as it was.
+#[derive(Parser, Debug)]
+struct Args {
+    /// Postgres connection URL, instead of the one in the config
+    #[arg(long)]
+    database: Option<String>,
+
+    #[arg(long)]
+    config: Option<std::path::PathBuf>,
+
+    #[arg(long, default_value_t = 1000)]
+    products: usize,
+
+    #[arg(long, default_value_t = 100_000)]
+    sales: usize,
+
+    /// Comma separated methods to compare
+    #[arg(long, value_delimiter = ',', default_value = "row,insert,csv,binary")]
+    methods: Vec<BulkLoad>,
+
+    #[arg(long, default_value_t = store::DEFAULT_BATCH_SIZE)]
+    batch_size: usize,
+}
+
+fn main() {
+    let args = Args::parse();
+
+    if let Err(e) = run(args) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run(args: Args) -> Result<(), String> {
+    let database = match args.database {
+        Some(database) => database,
+        None => config::load_config(args.config.as_deref(), &[])?.config()?.postgresql.url(),
+    };
+    let mut store = PostgresStore::connect(&database)?;
+    store::migrate_up(&mut store, None)?;
+
+    let products = products(args.products);
+    let sales = sales(args.sales, args.products);
+    let rows = products.len() + sales.len();
+    println!("Loading {} products and {} sales in batches of {}", products.len(), sales.len(), args.batch_size);
+
+    let mut baseline: Option<Duration> = None;
+    for method in args.methods {
+        store.set_bulk_load(method);
+        let elapsed = time_load(&mut store, &products, &sales, args.batch_size)?;
+        let speedup = baseline.map_or(1.0, |baseline| baseline.as_secs_f64() / elapsed.as_secs_f64());
+        baseline.get_or_insert(elapsed);
+        println!(
+            "{:<8} {:>9.3}s {:>10.0} rows/s {:>7.1}x",
+            method.name(),
+            elapsed.as_secs_f64(),
+            rows as f64 / elapsed.as_secs_f64(),
+            speedup
+        );
+    }
+    Ok(())
+}
+
+fn time_load(store: &mut PostgresStore, products: &[Product], sales: &[Sale], batch_size: usize) -> Result<Duration, String> {
+    store.begin()?;
+    let result = load(store, products, sales, batch_size);
+    store.rollback()?;
+    result
+}
+
+fn load(store: &mut PostgresStore, products: &[Product], sales: &[Sale], batch_size: usize) -> Result<Duration, String> {
+    // Start from empty tables so every method inserts the same rows. Unlike
+    // DELETE, a TRUNCATE rolled back leaves no dead rows to slow down the
+    // next run
+    store
+        .client()
+        .batch_execute("TRUNCATE sales, products")
+        .map_err(|e| e.to_string())?;
+
+    let start = Instant::now();
+    let mut writer = store::BatchWriter::new(store, LoadMode::Insert, batch_size);
+    for product in products.iter() {
+        writer.push_product(product.clone())?;
+    }
+    for sale in sales.iter() {
+        writer.push_sale(sale.clone())?;
+    }
+    let report = writer.finish()?;
+    let elapsed = start.elapsed();
+
+    if report.products.inserted != products.len() || report.sales.inserted != sales.len() {
+        return Err(format!("expected every row to be inserted, got:\n{}", report));
+    }
+    Ok(elapsed)
+}
+
+fn products(count: usize) -> Vec<Product> {
+    (1..=count as i32)
+        .map(|id| Product::new(id, &format!("category {}", id % 10), &format!("product {}", id)))
+        .collect()
+}
+
+fn sales(count: usize, products: usize) -> Vec<Sale> {
+    let units = [Unit::Piece, Unit::Kilogram, Unit::Litre];
+    (0..count)
+        .map(|n| {
+            let product_id = (n * 7919 % products.max(1)) as i32 + 1;
+            let date = Timestamp::from_epoch(1_577_836_800 + n as i64 * 60).unwrap_or_default();
+            let unit = units[n % units.len()];
+            let quantity = Decimal::new((n % 10_000) as i64, if unit == Unit::Piece { 0 } else { 3 });
+            Sale::new(&format!("bench-{}", n), product_id, date, quantity, unit)
+        })
+        .collect()
+}
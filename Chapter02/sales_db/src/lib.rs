@@ -0,0 +1,360 @@
+use clap::{Parser, Subcommand};
+use sales_model::config::{self, Config};
+use sales_model::csv::{self, CsvOptions};
+use sales_model::store::{self, Backend, BulkLoad, SalesStore};
+use sales_model::timestamp;
+use sales_model::{
+    json_records, jsonl, read_any, DateRange, Format, GroupBy, LoadMode, LoadReport, Product, QuantityRules, Record, Report,
+    ReportFormat, Sale, SalesAndProducts, TimestampFormat, WithFormat,
+};
+use std::path::Path;
+
+#[derive(Parser, Debug)]
+pub struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Database backend: sqlite, postgres, redis or memory
+    #[arg(long)]
+    backend: Option<Backend>,
+
+    /// Config file, or a directory holding config.toml, with the [sqlite],
+    /// [postgresql] and [redis] connection settings
+    #[arg(long)]
+    config: Option<std::path::PathBuf>,
+
+    /// Override a config value, e.g. --set postgresql.port=5433
+    #[arg(long = "set", value_name = "KEY=VALUE")]
+    overrides: Vec<String>,
+
+    /// SQLite database file, or Postgres or Redis connection URL, overriding
+    /// the config
+    #[arg(long)]
+    database: Option<String>,
+
+    /// How sale dates are printed: epoch, rfc3339 or a strftime pattern
+    #[arg(long, global = true, default_value = "epoch")]
+    date_format: TimestampFormat,
+
+    /// Sales JSON file, read one record at a time; its `products` array
+    /// must come before `sales`. JSON Lines files (.jsonl or .ndjson) and
+    /// directories holding products.jsonl and sales.jsonl are read too
+    #[arg(long)]
+    json_file: Option<std::path::PathBuf>,
+
+    /// Load products from a CSV file instead of --json-file
+    #[arg(long, requires = "sales_csv")]
+    products_csv: Option<std::path::PathBuf>,
+
+    /// Load sales from a CSV file instead of --json-file
+    #[arg(long, requires = "products_csv")]
+    sales_csv: Option<std::path::PathBuf>,
+
+    #[arg(long, default_value = ",", value_parser = csv::parse_delimiter)]
+    csv_delimiter: u8,
+
+    /// insert, upsert (update existing rows) or sync (upsert and delete
+    /// rows missing from the input)
+    #[arg(long, default_value = "insert")]
+    mode: LoadMode,
+
+    /// Records written to the database at a time when loading
+    #[arg(long, default_value_t = store::DEFAULT_BATCH_SIZE)]
+    batch_size: usize,
+
+    #[arg(long, default_value = "binary", help = BulkLoad::HELP)]
+    bulk_load: BulkLoad,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Apply or revert schema migrations
+    Migrate {
+        #[command(subcommand)]
+        action: MigrateAction,
+    },
+    /// Compare two datasets by id and report added, removed and modified
+    /// records. Each source is a sales file or CSV directory, `db` for the
+    /// configured database, `sqlite:<file>`, or a postgres:// or redis:// URL
+    Diff {
+        old: String,
+        new: String,
+
+        /// Print the differences as JSON
+        #[arg(long)]
+        json: bool,
+
+        #[command(flatten)]
+        dates: DateFilter,
+    },
+    /// Total sale quantities by product, category, unit, day, week or month
+    Report {
+        /// Source of the data, as for diff
+        #[arg(default_value = "db")]
+        source: String,
+
+        /// Comma separated groupings; quantities are always split by unit
+        #[arg(long, value_delimiter = ',', default_value = "product")]
+        by: Vec<GroupBy>,
+
+        /// text, csv or json
+        #[arg(long, default_value = "text")]
+        format: ReportFormat,
+
+        #[command(flatten)]
+        dates: DateFilter,
+    },
+}
+
+/// Restricts the sales read to a date range, e.g. `--from 2020-01-01
+/// --to 2020-02-01` for January 2020.
+#[derive(clap::Args, Debug)]
+struct DateFilter {
+    /// Only sales on or after this date, in ISO 8601 or epoch seconds
+    #[arg(long)]
+    from: Option<String>,
+
+    /// Only sales before this date, in ISO 8601 or epoch seconds
+    #[arg(long)]
+    to: Option<String>,
+
+    /// Fixed UTC offset of the --from and --to dates written without one,
+    /// e.g. +02:00. Time zone names such as Europe/Madrid are not accepted
+    #[arg(long, default_value = "UTC")]
+    tz: String,
+}
+
+impl DateFilter {
+    fn range(&self) -> Result<DateRange, String> {
+        let offset = timestamp::parse_offset(&self.tz)?;
+        DateRange::parse(self.from.as_deref(), self.to.as_deref(), offset)
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum MigrateAction {
+    /// Apply pending migrations, up to --to when given
+    Up {
+        #[arg(long)]
+        to: Option<u32>,
+    },
+    /// Revert the latest migration, or every migration above --to
+    Down {
+        #[arg(long)]
+        to: Option<u32>,
+    },
+    /// Show applied and pending migrations
+    Status,
+}
+
+/// Entry point shared by the database binaries, which only differ in the
+/// backend used when `--backend` is not given.
+pub fn main_with_backend(default_backend: Backend) {
+    let args = Args::parse();
+
+    if let Err(e) = run(args, default_backend) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+pub fn run(args: Args, default_backend: Backend) -> Result<(), String> {
+    let backend = args.backend.unwrap_or(default_backend);
+    let config = config::load_config(args.config.as_deref(), &args.overrides)?.config()?;
+    let database = match args.database {
+        Some(database) => database,
+        None => database_location(backend, &config),
+    };
+
+    match args.command {
+        Some(Command::Migrate { action }) => {
+            let mut store = store::open(backend, &database)?;
+            return migrate(store.as_mut(), action);
+        }
+        Some(Command::Diff { old, new, json, dates }) => {
+            let range = dates.range()?;
+            let old = read_source(&old, backend, &database, &range)?;
+            let new = read_source(&new, backend, &database, &range)?;
+            let diff = sales_model::diff(&old, &new, &args.date_format)?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&diff).map_err(|e| e.to_string())?);
+            } else {
+                println!("{}", diff);
+            }
+            return Ok(());
+        }
+        Some(Command::Report { source, by, format, dates }) => {
+            let data = read_source(&source, backend, &database, &dates.range()?)?;
+            let report = Report::new(&data, &by, &config.quantity)?;
+            println!("{}", report.render(format)?.trim_end());
+            return Ok(());
+        }
+        None => {}
+    }
+
+    let mut store;
+    let load_report;
+
+    if let (Some(products_csv), Some(sales_csv)) = (args.products_csv, args.sales_csv) {
+        let products_path = get_input_file(Some(products_csv), "products-csv")?;
+        let sales_path = get_input_file(Some(sales_csv), "sales-csv")?;
+        let options = CsvOptions {
+            delimiter: args.csv_delimiter,
+            ..Default::default()
+        };
+        store = open_my_db(backend, &database, args.bulk_load)?;
+        load_report = load_csv(
+            store.as_mut(),
+            &products_path,
+            &sales_path,
+            &options,
+            &config.quantity,
+            args.mode,
+            args.batch_size,
+        )?;
+    } else {
+        let input_path = get_input_file(args.json_file, "json-file")?;
+        store = open_my_db(backend, &database, args.bulk_load)?;
+        load_report = load_json(store.as_mut(), &input_path, &config.quantity, args.mode, args.batch_size)?;
+    }
+
+    println!("{}", load_report);
+
+    print_db(store.as_mut(), &args.date_format)
+}
+
+/// The database file or connection string of `backend` in the config.
+pub fn database_location(backend: Backend, config: &Config) -> String {
+    match backend {
+        Backend::Sqlite => config.sqlite.db_file.clone(),
+        Backend::Postgres => config.postgresql.url(),
+        Backend::Redis => config.redis.url(),
+        Backend::Memory => String::new(),
+    }
+}
+
+fn get_input_file(
+    option: Option<std::path::PathBuf>,
+    arg_name: &str,
+) -> Result<std::path::PathBuf, String> {
+    match option {
+        Some(path) => {
+            if path.exists() {
+                Ok(path)
+            } else {
+                Err(format!("{} does not exist", arg_name))
+            }
+        }
+        None => Err(format!("{} is required", arg_name)),
+    }
+}
+
+/// Opens the store and brings its schema up to date.
+pub fn open_my_db(backend: Backend, database: &str, bulk_load: BulkLoad) -> Result<Box<dyn SalesStore>, String> {
+    let mut store = store::open_with(backend, database, bulk_load)?;
+    store::migrate_up(store.as_mut(), None)?;
+    Ok(store)
+}
+
+fn read_source(source: &str, backend: Backend, database: &str, range: &DateRange) -> Result<SalesAndProducts, String> {
+    let mut store = if source == "db" {
+        store::open(backend, database)?
+    } else if let Some(path) = source.strip_prefix("sqlite:") {
+        // Opening would create an empty database
+        if !Path::new(path).exists() {
+            return Err(format!("{} does not exist", path));
+        }
+        store::open(Backend::Sqlite, path)?
+    } else if source.starts_with("postgres://") || source.starts_with("postgresql://") {
+        store::open(Backend::Postgres, source)?
+    } else if source.starts_with("redis://") {
+        store::open(Backend::Redis, source)?
+    } else {
+        let mut data: SalesAndProducts = read_any(source, None)?;
+        data.retain_dates(range);
+        return Ok(data);
+    };
+    store::query(store.as_mut(), range)
+}
+
+fn migrate(store: &mut dyn SalesStore, action: MigrateAction) -> Result<(), String> {
+    match action {
+        MigrateAction::Up { to } => {
+            for migration in store::migrate_up(store, to)? {
+                println!("Applied {:04} {}", migration.version, migration.name);
+            }
+        }
+        MigrateAction::Down { to } => {
+            for migration in store::migrate_down(store, to)? {
+                println!("Reverted {:04} {}", migration.version, migration.name);
+            }
+        }
+        MigrateAction::Status => {}
+    }
+
+    let current = store.schema_version()?;
+    println!("Schema version: {}", current);
+    for migration in store.migrations().iter() {
+        let state = if migration.version <= current { "applied" } else { "pending" };
+        println!("{:04} {:<20} {}", migration.version, migration.name, state);
+    }
+    Ok(())
+}
+
+// Rows are written as they are read, without holding the CSV files in memory
+fn load_csv(
+    store: &mut dyn SalesStore,
+    products_path: &Path,
+    sales_path: &Path,
+    options: &CsvOptions,
+    rules: &QuantityRules,
+    mode: LoadMode,
+    batch_size: usize,
+) -> Result<LoadReport, String> {
+    // Products go first so the sales can refer to them
+    let products = csv::records::<Product, _>(products_path, options)?.map(|product| product.map(Record::Product));
+    let sales = csv::records::<Sale, _>(sales_path, options)?.map(|sale| sale.map(Record::Sale));
+    load_records(store, products.chain(sales), rules, mode, batch_size)
+}
+
+fn load_json(
+    store: &mut dyn SalesStore,
+    input_path: &Path,
+    rules: &QuantityRules,
+    mode: LoadMode,
+    batch_size: usize,
+) -> Result<LoadReport, String> {
+    // Directories always hold JSON Lines files, even without products.jsonl
+    if input_path.is_dir() || Format::from_path(input_path) == Ok(Format::JsonLines) {
+        load_records(store, jsonl::stream(input_path)?, rules, mode, batch_size)
+    } else {
+        load_records(store, json_records(input_path)?, rules, mode, batch_size)
+    }
+}
+
+fn load_records<I: IntoIterator<Item = Result<Record, String>>>(
+    store: &mut dyn SalesStore,
+    records: I,
+    rules: &QuantityRules,
+    mode: LoadMode,
+    batch_size: usize,
+) -> Result<LoadReport, String> {
+    let (report, rounded) = store::load_records(store, records, rules, mode, batch_size)?;
+    if rounded > 0 {
+        println!("Rounded {} sale quantities to the scale of their unit", rounded);
+    }
+    Ok(report)
+}
+
+fn print_db(store: &mut dyn SalesStore, date_format: &TimestampFormat) -> Result<(), String> {
+    for product in store.products()? {
+        println!("Found product {}", serde_json::to_string(&product).map_err(|e| e.to_string())?);
+    }
+
+    for sale in store.sales(&DateRange::default())? {
+        let sale = serde_json::to_string(&WithFormat::new(&sale, date_format)).map_err(|e| e.to_string())?;
+        println!("Found sale {}", sale);
+    }
+
+    Ok(())
+}
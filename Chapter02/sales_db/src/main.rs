@@ -0,0 +1,5 @@
+use sales_model::store::Backend;
+
+fn main() {
+    sales_db::main_with_backend(Backend::Sqlite);
+}
@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS products (
+    id              INTEGER PRIMARY KEY,
+    category        VARCHAR(20) NOT NULL,
+    name            VARCHAR(20) NOT NULL
+);
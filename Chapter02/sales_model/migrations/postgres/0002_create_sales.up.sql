@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS sales (
+    id              VARCHAR(20) PRIMARY KEY,
+    product_id      INTEGER NOT NULL,
+    date            BIGINT NOT NULL,
+    quantity        DOUBLE PRECISION NOT NULL,
+    unit            VARCHAR(10) NOT NULL,
+    FOREIGN KEY(product_id) REFERENCES products(id)
+);
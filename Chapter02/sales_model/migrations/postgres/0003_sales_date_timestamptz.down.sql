@@ -0,0 +1 @@
+ALTER TABLE sales ALTER COLUMN date TYPE BIGINT USING EXTRACT(EPOCH FROM date)::BIGINT;
@@ -0,0 +1 @@
+ALTER TABLE sales ALTER COLUMN date TYPE TIMESTAMPTZ USING to_timestamp(date);
@@ -0,0 +1 @@
+ALTER TABLE sales ALTER COLUMN quantity TYPE DOUBLE PRECISION USING quantity::DOUBLE PRECISION;
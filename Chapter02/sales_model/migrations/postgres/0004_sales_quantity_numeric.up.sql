@@ -0,0 +1 @@
+ALTER TABLE sales ALTER COLUMN quantity TYPE NUMERIC USING quantity::NUMERIC;
@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS products (
+    id              INTEGER PRIMARY KEY,
+    category        TEXT NOT NULL,
+    name            TEXT NOT NULL
+);
@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS sales (
+    id              TEXT PRIMARY KEY,
+    product_id      INTEGER NOT NULL,
+    date            INTEGER NOT NULL,
+    quantity        REAL NOT NULL,
+    unit            TEXT NOT NULL,
+    FOREIGN KEY(product_id) REFERENCES products(id)
+);
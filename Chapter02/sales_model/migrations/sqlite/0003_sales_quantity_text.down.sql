@@ -0,0 +1,12 @@
+CREATE TABLE sales_new (
+    id              TEXT PRIMARY KEY,
+    product_id      INTEGER NOT NULL,
+    date            INTEGER NOT NULL,
+    quantity        REAL NOT NULL,
+    unit            TEXT NOT NULL,
+    FOREIGN KEY(product_id) REFERENCES products(id)
+);
+INSERT INTO sales_new (id, product_id, date, quantity, unit)
+    SELECT id, product_id, date, CAST(quantity AS REAL), unit FROM sales;
+DROP TABLE sales;
+ALTER TABLE sales_new RENAME TO sales;
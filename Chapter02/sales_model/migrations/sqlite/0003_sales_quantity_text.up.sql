@@ -0,0 +1,13 @@
+-- SQLite has no decimal type, so quantities are kept as their exact text
+CREATE TABLE sales_new (
+    id              TEXT PRIMARY KEY,
+    product_id      INTEGER NOT NULL,
+    date            INTEGER NOT NULL,
+    quantity        TEXT NOT NULL,
+    unit            TEXT NOT NULL,
+    FOREIGN KEY(product_id) REFERENCES products(id)
+);
+INSERT INTO sales_new (id, product_id, date, quantity, unit)
+    SELECT id, product_id, date, CAST(quantity AS TEXT), unit FROM sales;
+DROP TABLE sales;
+ALTER TABLE sales_new RENAME TO sales;
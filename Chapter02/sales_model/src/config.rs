@@ -0,0 +1,567 @@
+use crate::quantity::{QuantityRules, Rounding, MAX_SCALE};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use toml::{Spanned, Table, Value};
+
+pub const CONFIG_FILE: &str = "config.toml";
+
+/// Prefix of the environment variables overriding config values, as in
+/// `SALES_POSTGRESQL__PASSWORD` for `postgresql.password`.
+pub const ENV_PREFIX: &str = "SALES_";
+
+const MASK: &str = "********";
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct Config {
+    pub input: Input,
+    pub redis: Redis,
+    pub sqlite: SQLite,
+    pub postgresql: Postgresql,
+    pub quantity: QuantityRules,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct Input {
+    pub xml_file: String,
+    pub json_file: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Redis {
+    pub host: String,
+}
+
+impl Default for Redis {
+    fn default() -> Self {
+        Redis {
+            host: "localhost".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct SQLite {
+    pub db_file: String,
+}
+
+impl Default for SQLite {
+    fn default() -> Self {
+        SQLite {
+            db_file: "./my_db.db3".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Postgresql {
+    pub username: String,
+    pub password: String,
+    pub host: String,
+    pub port: u16,
+    pub database: String,
+}
+
+impl Default for Postgresql {
+    fn default() -> Self {
+        Postgresql {
+            username: "rorra".to_string(),
+            password: "password".to_string(),
+            host: "localhost".to_string(),
+            port: 5432,
+            database: "rust2018".to_string(),
+        }
+    }
+}
+
+impl Redis {
+    /// Connection URL; `host` may include a port, as in `cache.local:6380`.
+    pub fn url(&self) -> String {
+        format!("redis://{}/", self.host)
+    }
+}
+
+impl Postgresql {
+    /// Connection string in URL form, with the credentials percent-encoded.
+    pub fn url(&self) -> String {
+        format!(
+            "postgresql://{}:{}@{}:{}/{}",
+            encode(&self.username),
+            encode(&self.password),
+            self.host,
+            self.port,
+            encode(&self.database)
+        )
+    }
+}
+
+fn encode(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for byte in s.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Resolves `path` to a config file: directories are looked up for a
+/// `config.toml` inside them.
+pub fn config_file<P: AsRef<Path>>(path: P) -> PathBuf {
+    let path = path.as_ref();
+    if path.is_dir() {
+        path.join(CONFIG_FILE)
+    } else {
+        path.to_path_buf()
+    }
+}
+
+pub fn read_config<P: AsRef<Path>>(path: P) -> Result<Config, String> {
+    let mut config = LayeredConfig::new();
+    config.merge_file(path)?;
+    config.config()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Kind {
+    Text,
+    Port,
+    /// A file that must exist, relative to the config file
+    InputFile,
+    /// A database file created on demand, so only its directory must exist
+    DbFile,
+    /// Decimal places, up to [`MAX_SCALE`]
+    Scale,
+    Rounding,
+}
+
+struct Field {
+    key: &'static str,
+    kind: Kind,
+    required: bool,
+}
+
+const fn field(key: &'static str, kind: Kind, required: bool) -> Field {
+    Field { key, kind, required }
+}
+
+const SCHEMA: &[Field] = &[
+    field("input.xml_file", Kind::InputFile, true),
+    field("input.json_file", Kind::InputFile, true),
+    field("redis.host", Kind::Text, false),
+    field("sqlite.db_file", Kind::DbFile, false),
+    field("postgresql.username", Kind::Text, false),
+    field("postgresql.password", Kind::Text, false),
+    field("postgresql.host", Kind::Text, false),
+    field("postgresql.port", Kind::Port, false),
+    field("postgresql.database", Kind::Text, false),
+    field("quantity.rounding", Kind::Rounding, false),
+    field("quantity.piece_scale", Kind::Scale, false),
+    field("quantity.kilogram_scale", Kind::Scale, false),
+    field("quantity.gram_scale", Kind::Scale, false),
+    field("quantity.pound_scale", Kind::Scale, false),
+    field("quantity.litre_scale", Kind::Scale, false),
+    field("quantity.millilitre_scale", Kind::Scale, false),
+];
+
+/// A problem found by [`LayeredConfig::validate`], located in the layer that
+/// set the offending value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConfigIssue {
+    pub location: String,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.location, self.message)
+    }
+}
+
+/// Where a config value came from, in increasing order of precedence.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Layer {
+    Default,
+    File,
+    Env,
+    Cli,
+}
+
+impl Layer {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Layer::Default => "default",
+            Layer::File => "file",
+            Layer::Env => "env",
+            Layer::Cli => "cli",
+        }
+    }
+}
+
+impl fmt::Display for Layer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A config built from the defaults, a file, environment variables and
+/// `key=value` overrides, remembering which layer set each value.
+#[derive(Clone, Debug)]
+pub struct LayeredConfig {
+    table: Table,
+    sources: BTreeMap<String, Layer>,
+    /// `file:line:column`, environment variable or option that set each key
+    locations: BTreeMap<String, String>,
+    file: Option<PathBuf>,
+}
+
+impl Default for LayeredConfig {
+    fn default() -> Self {
+        let table = match Value::try_from(Config::default()) {
+            Ok(Value::Table(table)) => table,
+            _ => Table::new(),
+        };
+        let mut sources = BTreeMap::new();
+        for (key, _) in leaves(&table) {
+            sources.insert(key, Layer::Default);
+        }
+        LayeredConfig {
+            table,
+            sources,
+            locations: BTreeMap::new(),
+            file: None,
+        }
+    }
+}
+
+impl LayeredConfig {
+    pub fn new() -> Self {
+        LayeredConfig::default()
+    }
+
+    pub fn merge_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), String> {
+        let file_path = config_file(path);
+        let contents = std::fs::read_to_string(&file_path)
+            .map_err(|e| format!("{}: {}", file_path.display(), e))?;
+        let locate = |span: Range<usize>| {
+            let (line, column) = line_column(&contents, span.start);
+            format!("{}:{}:{}", file_path.display(), line, column)
+        };
+
+        // The config is made of sections of keys, so two levels of spans
+        // locate every value
+        let sections: BTreeMap<String, Spanned<BTreeMap<String, Spanned<Value>>>> =
+            toml::from_str(&contents).map_err(|e| match e.span() {
+                Some(span) => format!("{}: {}", locate(span), e.message().trim_end().replace('\n', ", ")),
+                None => format!("{}: {}", file_path.display(), e.message().trim_end().replace('\n', ", ")),
+            })?;
+
+        for (section, keys) in sections {
+            self.locations.insert(section.clone(), locate(keys.span()));
+            for (name, value) in keys.into_inner() {
+                let location = locate(value.span());
+                let value = value.into_inner();
+                let mut entries = vec![];
+                match value {
+                    Value::Table(ref table) => collect_leaves(table, &format!("{}.{}", section, name), &mut entries),
+                    value => entries.push((format!("{}.{}", section, name), value)),
+                }
+                for (key, value) in entries {
+                    insert(&mut self.table, &key, value);
+                    self.sources.insert(key.clone(), Layer::File);
+                    self.locations.insert(key, location.clone());
+                }
+            }
+        }
+
+        self.file = Some(file_path);
+        Ok(())
+    }
+
+    /// Applies the `SALES_SECTION__KEY` variables among `vars`. Variables
+    /// without a `__` separator are not config overrides and are skipped.
+    pub fn merge_env<I>(&mut self, vars: I) -> Result<(), String>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        for (name, value) in vars {
+            let key = match name.strip_prefix(ENV_PREFIX) {
+                Some(key) if key.contains("__") => key.to_ascii_lowercase().replace("__", "."),
+                _ => continue,
+            };
+            self.set_value(&key, &value, Layer::Env)
+                .map_err(|e| format!("{}: {}", name, e))?;
+            self.locations.insert(key, name);
+        }
+        Ok(())
+    }
+
+    /// Applies a `key=value` override, as in `postgresql.port=5433`.
+    pub fn set(&mut self, assignment: &str) -> Result<(), String> {
+        let (key, value) = assignment
+            .split_once('=')
+            .ok_or_else(|| format!("invalid override `{}`, expected KEY=VALUE", assignment))?;
+        self.set_value(key.trim(), value.trim(), Layer::Cli)?;
+        self.locations.insert(key.trim().to_string(), format!("--set {}", assignment));
+        Ok(())
+    }
+
+    fn set_value(&mut self, key: &str, raw: &str, layer: Layer) -> Result<(), String> {
+        let value = match get(&self.table, key) {
+            Some(current) => parse_value(key, raw, current)?,
+            None => return Err(format!("unknown config key `{}`", key)),
+        };
+        insert(&mut self.table, key, value);
+        self.sources.insert(key.to_string(), layer);
+        Ok(())
+    }
+
+    /// The effective config, with relative paths from the file resolved
+    /// against its directory.
+    pub fn config(&self) -> Result<Config, String> {
+        let mut table = self.table.clone();
+        for field in SCHEMA.iter().filter(|field| matches!(field.kind, Kind::InputFile | Kind::DbFile)) {
+            if let Some(path) = self.path(field.key) {
+                insert(&mut table, field.key, Value::String(path.display().to_string()));
+            }
+        }
+        Value::Table(table)
+            .try_into()
+            .map_err(|e: toml::de::Error| e.to_string())
+    }
+
+    pub fn source(&self, key: &str) -> Option<Layer> {
+        self.sources.get(key).copied()
+    }
+
+    /// Where the value of `key` was set, or where a missing key belongs.
+    pub fn location(&self, key: &str) -> String {
+        let section = key.split('.').next().unwrap_or(key);
+        match (self.locations.get(key), self.locations.get(section), &self.file) {
+            (Some(location), _, _) | (None, Some(location), _) => location.clone(),
+            (None, None, Some(file)) => file.display().to_string(),
+            (None, None, None) => "config".to_string(),
+        }
+    }
+
+    /// A path value, relative to the config file when the file set it.
+    pub fn path(&self, key: &str) -> Option<PathBuf> {
+        let path = match get(&self.table, key)? {
+            Value::String(path) if !path.is_empty() => Path::new(path),
+            _ => return None,
+        };
+        let base = match (&self.file, self.source(key)) {
+            (Some(file), Some(Layer::File)) if path.is_relative() => file.parent(),
+            _ => None,
+        };
+        match base {
+            Some(base) => Some(base.join(path)),
+            None => Some(path.to_path_buf()),
+        }
+    }
+
+    /// Checks types, required keys and paths against the config schema.
+    pub fn validate(&self) -> Vec<ConfigIssue> {
+        let mut issues = vec![];
+        let mut issue = |key: &str, message: String| {
+            issues.push(ConfigIssue {
+                location: self.location(key),
+                message,
+            })
+        };
+
+        for field in SCHEMA.iter() {
+            let key = field.key;
+            if field.required && self.source(key).unwrap_or(Layer::Default) == Layer::Default {
+                issue(key, format!("missing required key `{}`", key));
+                continue;
+            }
+
+            let value = match get(&self.table, key) {
+                Some(value) => value,
+                None => continue,
+            };
+            match (field.kind, value) {
+                (Kind::Port, Value::Integer(port)) => {
+                    if !(1..=u16::MAX as i64).contains(port) {
+                        issue(key, format!("`{}` must be between 1 and {}, found {}", key, u16::MAX, port));
+                    }
+                }
+                (Kind::Scale, Value::Integer(scale)) => {
+                    if !(0..=MAX_SCALE as i64).contains(scale) {
+                        issue(key, format!("`{}` must be between 0 and {}, found {}", key, MAX_SCALE, scale));
+                    }
+                }
+                (Kind::Port | Kind::Scale, value) => {
+                    issue(key, format!("`{}` must be an integer, found {}", key, value.type_str()));
+                }
+                (Kind::Rounding, Value::String(rounding)) => {
+                    if let Err(e) = rounding.parse::<Rounding>() {
+                        issue(key, e);
+                    }
+                }
+                (_, Value::String(_)) => {}
+                (_, value) => {
+                    issue(key, format!("`{}` must be a string, found {}", key, value.type_str()));
+                    continue;
+                }
+            }
+
+            let path = match self.path(key) {
+                Some(path) => path,
+                None => continue,
+            };
+            match field.kind {
+                Kind::InputFile if !path.is_file() => {
+                    issue(key, format!("`{}` file {} does not exist", key, path.display()));
+                }
+                Kind::DbFile => {
+                    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty());
+                    if let Some(dir) = dir.filter(|dir| !dir.is_dir()) {
+                        issue(key, format!("`{}` directory {} does not exist", key, dir.display()));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        for (key, _) in leaves(&self.table) {
+            if !SCHEMA.iter().any(|field| field.key == key) {
+                issue(&key, format!("unknown key `{}`", key));
+            }
+        }
+
+        issues
+    }
+
+    /// Every value by dotted key, in key order, with its layer.
+    pub fn entries(&self) -> Vec<(String, Value, Layer)> {
+        leaves(&self.table)
+            .into_iter()
+            .map(|(key, value)| {
+                let layer = self.source(&key).unwrap_or(Layer::Default);
+                (key, value, layer)
+            })
+            .collect()
+    }
+
+    /// The effective config with secret values masked.
+    pub fn masked(&self) -> Table {
+        let mut table = self.table.clone();
+        for (key, _) in leaves(&self.table) {
+            if is_secret(&key) {
+                insert(&mut table, &key, Value::String(MASK.to_string()));
+            }
+        }
+        table
+    }
+}
+
+/// Builds a config from the defaults, the optional file, the process
+/// environment and `overrides`, in that order.
+pub fn load_config(path: Option<&Path>, overrides: &[String]) -> Result<LayeredConfig, String> {
+    let mut config = LayeredConfig::new();
+    if let Some(path) = path {
+        config.merge_file(path)?;
+    }
+    config.merge_env(std::env::vars())?;
+    for assignment in overrides {
+        config.set(assignment)?;
+    }
+    Ok(config)
+}
+
+pub fn is_secret(key: &str) -> bool {
+    let name = key.rsplit('.').next().unwrap_or(key);
+    ["password", "secret", "token"].iter().any(|secret| name.contains(secret))
+}
+
+/// Formats a value for display, hiding it if the key holds a secret.
+pub fn display_value(key: &str, value: &Value) -> String {
+    if is_secret(key) {
+        MASK.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+// Overrides keep the type of the value they replace, so `5433` stays a
+// string where the schema expects one.
+fn parse_value(key: &str, raw: &str, current: &Value) -> Result<Value, String> {
+    let invalid = |e: &dyn fmt::Display| format!("invalid value `{}` for `{}`: {}", raw, key, e);
+    match current {
+        Value::String(_) => Ok(Value::String(raw.to_string())),
+        Value::Integer(_) => raw.parse().map(Value::Integer).map_err(|e| invalid(&e)),
+        Value::Float(_) => raw.parse().map(Value::Float).map_err(|e| invalid(&e)),
+        Value::Boolean(_) => raw.parse().map(Value::Boolean).map_err(|e| invalid(&e)),
+        Value::Table(_) => Err(format!("`{}` is a section, not a value", key)),
+        Value::Array(_) | Value::Datetime(_) => {
+            let mut table: Table = format!("value = {}", raw).parse().map_err(|e| invalid(&e))?;
+            table.remove("value").ok_or_else(|| invalid(&"missing value"))
+        }
+    }
+}
+
+// 1-based line and column of a byte offset
+fn line_column(contents: &str, offset: usize) -> (usize, usize) {
+    let before = &contents[..offset.min(contents.len())];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    (line, before[line_start..].chars().count() + 1)
+}
+
+fn leaves(table: &Table) -> Vec<(String, Value)> {
+    let mut out = vec![];
+    collect_leaves(table, "", &mut out);
+    out
+}
+
+fn collect_leaves(table: &Table, prefix: &str, out: &mut Vec<(String, Value)>) {
+    for (name, value) in table.iter() {
+        let key = if prefix.is_empty() {
+            name.clone()
+        } else {
+            format!("{}.{}", prefix, name)
+        };
+        match value {
+            Value::Table(child) => collect_leaves(child, &key, out),
+            _ => out.push((key, value.clone())),
+        }
+    }
+}
+
+fn get<'a>(table: &'a Table, key: &str) -> Option<&'a Value> {
+    let (section, name) = match key.rsplit_once('.') {
+        Some((section, name)) => (Some(section), name),
+        None => (None, key),
+    };
+    match section {
+        Some(section) => match get(table, section)? {
+            Value::Table(child) => child.get(name),
+            _ => None,
+        },
+        None => table.get(name),
+    }
+}
+
+fn insert(table: &mut Table, key: &str, value: Value) {
+    match key.split_once('.') {
+        Some((section, rest)) => {
+            let child = table
+                .entry(section.to_string())
+                .or_insert_with(|| Value::Table(Table::new()));
+            if !child.is_table() {
+                *child = Value::Table(Table::new());
+            }
+            if let Value::Table(child) = child {
+                insert(child, rest, value);
+            }
+        }
+        None => {
+            table.insert(key.to_string(), value);
+        }
+    }
+}
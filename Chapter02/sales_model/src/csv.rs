@@ -0,0 +1,174 @@
+use crate::{Product, Sale, SalesAndProducts, TimestampFormat, WithFormat};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs::File;
+use std::path::Path;
+use std::str::FromStr;
+
+pub const PRODUCTS_FILE: &str = "products.csv";
+pub const SALES_FILE: &str = "sales.csv";
+
+/// When fields are wrapped in quotes on output. Reading always accepts
+/// quoted fields.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Quoting {
+    #[default]
+    Necessary,
+    Always,
+    NonNumeric,
+    Never,
+}
+
+impl FromStr for Quoting {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "necessary" => Ok(Quoting::Necessary),
+            "always" => Ok(Quoting::Always),
+            "non-numeric" => Ok(Quoting::NonNumeric),
+            "never" => Ok(Quoting::Never),
+            _ => Err(format!(
+                "unknown quoting `{}`, expected one of: necessary, always, non-numeric, never",
+                s
+            )),
+        }
+    }
+}
+
+impl From<Quoting> for ::csv::QuoteStyle {
+    fn from(quoting: Quoting) -> Self {
+        match quoting {
+            Quoting::Necessary => ::csv::QuoteStyle::Necessary,
+            Quoting::Always => ::csv::QuoteStyle::Always,
+            Quoting::NonNumeric => ::csv::QuoteStyle::NonNumeric,
+            Quoting::Never => ::csv::QuoteStyle::Never,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CsvOptions {
+    pub delimiter: u8,
+    pub quote: u8,
+    pub quoting: Quoting,
+    /// How sale dates are written
+    pub date_format: TimestampFormat,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        CsvOptions {
+            delimiter: b',',
+            quote: b'"',
+            quoting: Quoting::default(),
+            date_format: TimestampFormat::default(),
+        }
+    }
+}
+
+impl CsvOptions {
+    fn reader(&self, path: &Path) -> Result<::csv::Reader<File>, String> {
+        ::csv::ReaderBuilder::new()
+            .delimiter(self.delimiter)
+            .quote(self.quote)
+            .trim(::csv::Trim::All)
+            .from_path(path)
+            .map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    fn writer(&self, path: &Path) -> Result<::csv::Writer<File>, String> {
+        ::csv::WriterBuilder::new()
+            .delimiter(self.delimiter)
+            .quote(self.quote)
+            .quote_style(self.quoting.into())
+            .from_path(path)
+            .map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+/// Parses a delimiter given on the command line: a single character or `\t`.
+pub fn parse_delimiter(s: &str) -> Result<u8, String> {
+    match s {
+        "\\t" | "tab" => Ok(b'\t'),
+        _ if s.len() == 1 && s.is_ascii() => Ok(s.as_bytes()[0]),
+        _ => Err(format!("delimiter must be a single ASCII character, got `{}`", s)),
+    }
+}
+
+/// Iterates over the records of a CSV file. Columns are matched to fields by
+/// the header row, so their order in the file does not matter.
+pub fn records<T: DeserializeOwned, P: AsRef<Path>>(
+    input_path: P,
+    options: &CsvOptions,
+) -> Result<impl Iterator<Item = Result<T, String>>, String> {
+    let path = input_path.as_ref().to_path_buf();
+    let reader = options.reader(&path)?;
+    Ok(reader
+        .into_deserialize()
+        .map(move |record| record.map_err(|e| format!("{}: {}", path.display(), e))))
+}
+
+pub fn read_records<T: DeserializeOwned, P: AsRef<Path>>(
+    input_path: P,
+    options: &CsvOptions,
+) -> Result<Vec<T>, String> {
+    records(input_path, options)?.collect()
+}
+
+pub fn write_records<T: Serialize, P: AsRef<Path>>(
+    output_path: P,
+    records: &[T],
+    options: &CsvOptions,
+) -> Result<(), String> {
+    let path = output_path.as_ref();
+    let mut writer = options.writer(path)?;
+    for record in records.iter() {
+        writer.serialize(record).map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+    writer.flush().map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+pub fn read_products<P: AsRef<Path>>(input_path: P, options: &CsvOptions) -> Result<Vec<Product>, String> {
+    read_records(input_path, options)
+}
+
+pub fn read_sales<P: AsRef<Path>>(input_path: P, options: &CsvOptions) -> Result<Vec<Sale>, String> {
+    read_records(input_path, options)
+}
+
+pub fn write_products<P: AsRef<Path>>(
+    output_path: P,
+    products: &[Product],
+    options: &CsvOptions,
+) -> Result<(), String> {
+    write_records(output_path, products, options)
+}
+
+pub fn write_sales<P: AsRef<Path>>(output_path: P, sales: &[Sale], options: &CsvOptions) -> Result<(), String> {
+    let sales: Vec<WithFormat<Sale>> = sales.iter().map(|sale| WithFormat::new(sale, &options.date_format)).collect();
+    write_records(output_path, &sales, options)
+}
+
+/// Reads `products.csv` and `sales.csv` from a directory.
+pub fn read_csv_dir<S: DeserializeOwned, P: AsRef<Path>>(
+    input_dir: P,
+    options: &CsvOptions,
+) -> Result<SalesAndProducts<S>, String> {
+    let dir = input_dir.as_ref();
+    let products = read_products(dir.join(PRODUCTS_FILE), options)?;
+    let sales = read_records(dir.join(SALES_FILE), options)?;
+    Ok(SalesAndProducts::new(products, sales))
+}
+
+/// Writes `products.csv` and `sales.csv` into a directory, creating it if needed.
+pub fn write_csv_dir<P: AsRef<Path>>(
+    output_dir: P,
+    data: &SalesAndProducts,
+    options: &CsvOptions,
+) -> Result<(), String> {
+    let dir = output_dir.as_ref();
+    std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    write_products(dir.join(PRODUCTS_FILE), &data.products, options)?;
+    write_sales(dir.join(SALES_FILE), &data.sales, options)
+}
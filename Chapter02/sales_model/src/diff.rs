@@ -0,0 +1,264 @@
+use crate::{Product, Sale, SalesAndProducts, TimestampFormat, WithFormat};
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use serde_json::Value;
+use std::collections::btree_map::{BTreeMap, Entry};
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub old: Value,
+    pub new: Value,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Modified<K> {
+    pub id: K,
+    pub changes: Vec<FieldChange>,
+}
+
+/// Records of one kind matched by id between two datasets.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct RecordDiff<K, T> {
+    pub added: Vec<T>,
+    pub removed: Vec<T>,
+    pub modified: Vec<Modified<K>>,
+}
+
+impl<K, T> RecordDiff<K, T> {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+
+    fn summary(&self) -> String {
+        format!(
+            "{} added, {} removed, {} modified",
+            self.added.len(),
+            self.removed.len(),
+            self.modified.len()
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DatasetDiff {
+    pub products: RecordDiff<i32, Product>,
+    pub sales: RecordDiff<String, Sale>,
+    /// How the dates of the sales are written
+    pub date_format: TimestampFormat,
+}
+
+impl DatasetDiff {
+    pub fn is_empty(&self) -> bool {
+        self.products.is_empty() && self.sales.is_empty()
+    }
+
+    fn dated_sales(&self) -> RecordDiff<String, WithFormat<'_, Sale>> {
+        RecordDiff {
+            added: dated(&self.sales.added, &self.date_format),
+            removed: dated(&self.sales.removed, &self.date_format),
+            modified: self.sales.modified.clone(),
+        }
+    }
+}
+
+fn dated<'a>(sales: &'a [Sale], date_format: &'a TimestampFormat) -> Vec<WithFormat<'a, Sale>> {
+    sales.iter().map(|sale| WithFormat::new(sale, date_format)).collect()
+}
+
+impl Serialize for DatasetDiff {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("DatasetDiff", 2)?;
+        state.serialize_field("products", &self.products)?;
+        state.serialize_field("sales", &self.dated_sales())?;
+        state.end()
+    }
+}
+
+impl fmt::Display for DatasetDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_records(f, "product", &self.products, |product| product.id)?;
+        write_records(f, "sale", &self.dated_sales(), |sale| sale.value.id.clone())?;
+        write!(
+            f,
+            "Products: {}; sales: {}",
+            self.products.summary(),
+            self.sales.summary()
+        )
+    }
+}
+
+fn write_records<K, T, F>(f: &mut fmt::Formatter<'_>, name: &str, diff: &RecordDiff<K, T>, id: F) -> fmt::Result
+where
+    K: fmt::Display,
+    T: Serialize,
+    F: Fn(&T) -> K,
+{
+    for record in diff.added.iter() {
+        writeln!(f, "+ {} {} {}", name, id(record), to_json(record))?;
+    }
+    for record in diff.removed.iter() {
+        writeln!(f, "- {} {} {}", name, id(record), to_json(record))?;
+    }
+    for modified in diff.modified.iter() {
+        let changes: Vec<String> = modified
+            .changes
+            .iter()
+            .map(|change| format!("{} {} -> {}", change.field, change.old, change.new))
+            .collect();
+        writeln!(f, "~ {} {}: {}", name, modified.id, changes.join(", "))?;
+    }
+    Ok(())
+}
+
+fn to_json<T: Serialize>(record: &T) -> Value {
+    serde_json::to_value(record).unwrap_or(Value::Null)
+}
+
+/// Matches products and sales by id and reports what was added, removed
+/// and modified from `old` to `new`, field by field, with sale dates in
+/// `date_format`. Fails when an id appears twice in either dataset, since
+/// its records could not be matched.
+pub fn diff(old: &SalesAndProducts, new: &SalesAndProducts, date_format: &TimestampFormat) -> Result<DatasetDiff, String> {
+    let sale_json = |sale: &Sale| to_json(&WithFormat::new(sale, date_format));
+    Ok(DatasetDiff {
+        products: diff_records("product", &old.products, &new.products, |product| product.id, to_json)?,
+        sales: diff_records("sale", &old.sales, &new.sales, |sale| sale.id.clone(), sale_json)?,
+        date_format: date_format.clone(),
+    })
+}
+
+fn diff_records<K, T, F, J>(name: &str, old: &[T], new: &[T], id: F, json: J) -> Result<RecordDiff<K, T>, String>
+where
+    K: Ord + fmt::Display,
+    T: Clone + PartialEq,
+    F: Fn(&T) -> K,
+    J: Fn(&T) -> Value,
+{
+    let old = by_id(old, &id).map_err(|key| format!("{} {} appears more than once in the old data", name, key))?;
+    let new = by_id(new, &id).map_err(|key| format!("{} {} appears more than once in the new data", name, key))?;
+    let mut diff = RecordDiff {
+        added: vec![],
+        removed: vec![],
+        modified: vec![],
+    };
+
+    for (key, old_record) in old.iter() {
+        if !new.contains_key(key) {
+            diff.removed.push((*old_record).clone());
+        }
+    }
+    for (key, new_record) in new.into_iter() {
+        match old.get(&key) {
+            None => diff.added.push(new_record.clone()),
+            Some(old_record) if *old_record != new_record => diff.modified.push(Modified {
+                id: key,
+                changes: field_changes(json(old_record), json(new_record)),
+            }),
+            Some(_) => {}
+        }
+    }
+
+    Ok(diff)
+}
+
+// The first repeated id is the error
+fn by_id<'a, K: Ord, T, F: Fn(&T) -> K>(records: &'a [T], id: &F) -> Result<BTreeMap<K, &'a T>, K> {
+    let mut map = BTreeMap::new();
+    for record in records.iter() {
+        match map.entry(id(record)) {
+            Entry::Occupied(entry) => return Err(entry.remove_entry().0),
+            Entry::Vacant(entry) => {
+                entry.insert(record);
+            }
+        }
+    }
+    Ok(map)
+}
+
+// Compares the serialized form, so every field type is handled the same way
+fn field_changes(old: Value, new: Value) -> Vec<FieldChange> {
+    let (mut old, new) = match (old, new) {
+        (Value::Object(old), Value::Object(new)) => (old, new),
+        (old, new) => {
+            return vec![FieldChange {
+                field: String::new(),
+                old,
+                new,
+            }]
+        }
+    };
+
+    let mut changes = vec![];
+    for (field, new_value) in new.into_iter() {
+        let old_value = old.remove(&field).unwrap_or(Value::Null);
+        if old_value != new_value {
+            changes.push(FieldChange {
+                field,
+                old: old_value,
+                new: new_value,
+            });
+        }
+    }
+    for (field, old_value) in old.into_iter() {
+        changes.push(FieldChange {
+            field,
+            old: old_value,
+            new: Value::Null,
+        });
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Decimal, Timestamp, Unit};
+
+    fn sale(id: &str, quantity: i64) -> Sale {
+        Sale::new(id, 1, Timestamp::default(), Decimal::new(quantity, 0), Unit::Piece)
+    }
+
+    #[test]
+    fn records_are_matched_by_id() {
+        let old = SalesAndProducts::new(
+            vec![Product::new(1, "fruit", "apple"), Product::new(2, "fruit", "pear")],
+            vec![sale("a", 1), sale("b", 2)],
+        );
+        let new = SalesAndProducts::new(
+            vec![Product::new(2, "fruit", "pear"), Product::new(1, "fruit", "green apple")],
+            vec![sale("b", 3), sale("c", 1)],
+        );
+        let diff = diff(&old, &new, &TimestampFormat::Epoch).unwrap();
+
+        assert!(diff.products.added.is_empty() && diff.products.removed.is_empty());
+        assert_eq!(diff.products.modified[0].id, 1);
+        assert_eq!(diff.products.modified[0].changes[0].field, "name");
+        assert_eq!(diff.sales.added, [sale("c", 1)]);
+        assert_eq!(diff.sales.removed, [sale("a", 1)]);
+        assert_eq!(diff.sales.modified[0].id, "b");
+        assert_eq!(
+            diff.to_string().lines().last(),
+            Some("Products: 0 added, 0 removed, 1 modified; sales: 1 added, 1 removed, 1 modified")
+        );
+    }
+
+    #[test]
+    fn repeated_ids_are_an_error() {
+        let data = SalesAndProducts::new(vec![Product::new(1, "fruit", "apple")], vec![sale("a", 1)]);
+        let mut repeated = data.clone();
+        repeated.products.push(Product::new(1, "fruit", "pear"));
+        assert_eq!(
+            diff(&repeated, &data, &TimestampFormat::Epoch).unwrap_err(),
+            "product 1 appears more than once in the old data"
+        );
+
+        let mut repeated = data.clone();
+        repeated.sales.push(sale("a", 2));
+        assert_eq!(
+            diff(&data, &repeated, &TimestampFormat::Epoch).unwrap_err(),
+            "sale a appears more than once in the new data"
+        );
+    }
+}
@@ -0,0 +1,131 @@
+use crate::csv::{self, CsvOptions};
+use crate::{jsonl, read_json, write_json, xml, SalesAndProducts, TimestampFormat, WithFormat};
+use serde::de::DeserializeOwned;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Xml,
+    Toml,
+    /// A directory holding `products.csv` and `sales.csv`.
+    Csv,
+    /// JSON Lines: a file of tagged records, or a directory holding
+    /// `products.jsonl` and `sales.jsonl`.
+    JsonLines,
+}
+
+impl Format {
+    pub const ALL: [Format; 5] = [Format::Json, Format::Xml, Format::Toml, Format::Csv, Format::JsonLines];
+
+    /// Guesses the format from the file extension. Directories holding
+    /// `products.jsonl` are JSON Lines; other directories and paths without
+    /// an extension are taken as CSV directories.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Format, String> {
+        let path = path.as_ref();
+        if path.is_dir() {
+            if path.join(jsonl::PRODUCTS_FILE).exists() {
+                return Ok(Format::JsonLines);
+            }
+            return Ok(Format::Csv);
+        }
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) => ext.parse(),
+            None => Ok(Format::Csv),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Format::Json => "json",
+            Format::Xml => "xml",
+            Format::Toml => "toml",
+            Format::Csv => "csv",
+            Format::JsonLines => "jsonl",
+        }
+    }
+
+    /// Reads a file, with sales of any type laid out like [`Sale`](crate::Sale).
+    pub fn read<S: DeserializeOwned, P: AsRef<Path>>(&self, input_path: P) -> Result<SalesAndProducts<S>, String> {
+        match self {
+            Format::Json => read_json(input_path),
+            Format::Xml => xml::read_xml(input_path),
+            Format::Toml => read_toml(input_path),
+            Format::Csv => csv::read_csv_dir(input_path, &CsvOptions::default()),
+            Format::JsonLines => jsonl::read(input_path),
+        }
+    }
+
+    /// Writes `data` with the sale dates in `date_format`.
+    pub fn write<P: AsRef<Path>>(
+        &self,
+        output_path: P,
+        data: &SalesAndProducts,
+        date_format: &TimestampFormat,
+    ) -> Result<(), String> {
+        match self {
+            Format::Json => write_json(output_path, data, date_format),
+            Format::Xml => xml::write_xml(output_path, data, date_format),
+            Format::Toml => write_toml(output_path, data, date_format),
+            Format::Csv => {
+                let options = CsvOptions {
+                    date_format: date_format.clone(),
+                    ..Default::default()
+                };
+                csv::write_csv_dir(output_path, data, &options)
+            }
+            Format::JsonLines => jsonl::write(output_path, data, date_format),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("ndjson") {
+            return Ok(Format::JsonLines);
+        }
+        Format::ALL
+            .into_iter()
+            .find(|format| format.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                let names: Vec<&str> = Format::ALL.iter().map(|format| format.name()).collect();
+                format!("unknown format `{}`, expected one of: {}", s, names.join(", "))
+            })
+    }
+}
+
+/// Reads a file in the given format, or in the one guessed from its path.
+pub fn read_any<S: DeserializeOwned, P: AsRef<Path>>(
+    input_path: P,
+    format: Option<Format>,
+) -> Result<SalesAndProducts<S>, String> {
+    let format = match format {
+        Some(format) => format,
+        None => Format::from_path(&input_path)?,
+    };
+    format.read(input_path)
+}
+
+pub fn read_toml<S: DeserializeOwned, P: AsRef<Path>>(input_path: P) -> Result<SalesAndProducts<S>, String> {
+    let contents = std::fs::read_to_string(input_path).map_err(|e| e.to_string())?;
+    toml::from_str(&contents).map_err(|e| e.to_string())
+}
+
+pub fn write_toml<P: AsRef<Path>>(
+    output_path: P,
+    data: &SalesAndProducts,
+    date_format: &TimestampFormat,
+) -> Result<(), String> {
+    let contents = toml::to_string(&WithFormat::new(data, date_format)).map_err(|e| e.to_string())?;
+    std::fs::write(output_path, contents).map_err(|e| e.to_string())
+}
@@ -0,0 +1,209 @@
+use crate::stream::Record;
+use crate::{Product, Sale, SalesAndProducts, TimestampFormat, WithFormat};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+pub const PRODUCTS_FILE: &str = "products.jsonl";
+pub const SALES_FILE: &str = "sales.jsonl";
+
+// A tagged line borrowing its record, written like a `Record`
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum RecordRef<'a> {
+    Product(&'a Product),
+    Sale(WithFormat<'a, Sale>),
+}
+
+/// Iterates over a JSON Lines file, one value per line. Blank lines are
+/// skipped and errors give the line number.
+pub fn records<T: DeserializeOwned, P: AsRef<Path>>(
+    input_path: P,
+) -> Result<impl Iterator<Item = Result<T, String>>, String> {
+    let path = input_path.as_ref().to_path_buf();
+    let file = File::open(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(BufReader::new(file)
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| line.as_ref().map_or(true, |line| !line.trim().is_empty()))
+        .map(move |(index, line)| {
+            let line = line.map_err(|e| format!("{}: {}", path.display(), e))?;
+            serde_json::from_str(&line).map_err(|e| format!("{}: line {}: {}", path.display(), index + 1, e))
+        }))
+}
+
+pub fn read_records<T: DeserializeOwned, P: AsRef<Path>>(input_path: P) -> Result<Vec<T>, String> {
+    records(input_path)?.collect()
+}
+
+fn open(path: &Path, append: bool) -> Result<BufWriter<File>, String> {
+    let file = if append {
+        OpenOptions::new().create(true).append(true).open(path)
+    } else {
+        File::create(path)
+    };
+    file.map(BufWriter::new).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn write_lines<T: Serialize, I: IntoIterator<Item = T>>(path: &Path, records: I, append: bool) -> Result<(), String> {
+    let mut writer = open(path, append)?;
+    for record in records {
+        serde_json::to_writer(&mut writer, &record).map_err(|e| format!("{}: {}", path.display(), e))?;
+        writer.write_all(b"\n").map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+    writer.flush().map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+pub fn write_records<T: Serialize, P: AsRef<Path>>(output_path: P, records: &[T]) -> Result<(), String> {
+    write_lines(output_path.as_ref(), records, false)
+}
+
+/// Adds records to the end of a JSON Lines file, creating it if needed.
+pub fn append_records<T: Serialize, P: AsRef<Path>>(output_path: P, records: &[T]) -> Result<(), String> {
+    write_lines(output_path.as_ref(), records, true)
+}
+
+/// Iterates over a file of tagged records, such as
+/// `{"type":"product","id":1,...}`, in file order.
+pub fn tagged_records<P: AsRef<Path>>(input_path: P) -> Result<impl Iterator<Item = Result<Record, String>>, String> {
+    records(input_path)
+}
+
+/// Reads a file of tagged product and sale records, in any order.
+pub fn read_jsonl<S: DeserializeOwned, P: AsRef<Path>>(input_path: P) -> Result<SalesAndProducts<S>, String> {
+    let mut data = SalesAndProducts::new(vec![], vec![]);
+    for record in records::<Record<S>, _>(input_path)? {
+        match record? {
+            Record::Product(product) => data.products.push(product),
+            Record::Sale(sale) => data.sales.push(sale),
+        }
+    }
+    Ok(data)
+}
+
+fn tagged<'a>(data: &'a SalesAndProducts, date_format: &'a TimestampFormat) -> impl Iterator<Item = RecordRef<'a>> {
+    let products = data.products.iter().map(RecordRef::Product);
+    let sales = data.sales.iter().map(move |sale| RecordRef::Sale(WithFormat::new(sale, date_format)));
+    products.chain(sales)
+}
+
+/// Writes the products and then the sales as tagged records.
+pub fn write_jsonl<P: AsRef<Path>>(
+    output_path: P,
+    data: &SalesAndProducts,
+    date_format: &TimestampFormat,
+) -> Result<(), String> {
+    write_lines(output_path.as_ref(), tagged(data, date_format), false)
+}
+
+/// Like [`write_jsonl`], adding to the end of the file.
+pub fn append_jsonl<P: AsRef<Path>>(
+    output_path: P,
+    data: &SalesAndProducts,
+    date_format: &TimestampFormat,
+) -> Result<(), String> {
+    write_lines(output_path.as_ref(), tagged(data, date_format), true)
+}
+
+fn dated<'a>(sales: &'a [Sale], date_format: &'a TimestampFormat) -> impl Iterator<Item = WithFormat<'a, Sale>> {
+    sales.iter().map(move |sale| WithFormat::new(sale, date_format))
+}
+
+/// Iterates over `products.jsonl` and then `sales.jsonl` in a directory.
+/// Lines need no `type` tag there, and one is ignored if present.
+pub fn dir_records<P: AsRef<Path>>(input_dir: P) -> Result<impl Iterator<Item = Result<Record, String>>, String> {
+    let dir = input_dir.as_ref();
+    let products = records::<Product, _>(dir.join(PRODUCTS_FILE))?.map(|product| product.map(Record::Product));
+    let sales = records::<Sale, _>(dir.join(SALES_FILE))?.map(|sale| sale.map(Record::Sale));
+    Ok(products.chain(sales))
+}
+
+/// Reads `products.jsonl` and `sales.jsonl` from a directory.
+pub fn read_jsonl_dir<S: DeserializeOwned, P: AsRef<Path>>(input_dir: P) -> Result<SalesAndProducts<S>, String> {
+    let dir = input_dir.as_ref();
+    let products = read_records(dir.join(PRODUCTS_FILE))?;
+    let sales = read_records(dir.join(SALES_FILE))?;
+    Ok(SalesAndProducts::new(products, sales))
+}
+
+/// Writes `products.jsonl` and `sales.jsonl` into a directory, creating it if
+/// needed.
+pub fn write_jsonl_dir<P: AsRef<Path>>(
+    output_dir: P,
+    data: &SalesAndProducts,
+    date_format: &TimestampFormat,
+) -> Result<(), String> {
+    let dir = output_dir.as_ref();
+    std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    write_records(dir.join(PRODUCTS_FILE), &data.products)?;
+    write_lines(&dir.join(SALES_FILE), dated(&data.sales, date_format), false)
+}
+
+/// Like [`write_jsonl_dir`], adding to the end of the files.
+pub fn append_jsonl_dir<P: AsRef<Path>>(
+    output_dir: P,
+    data: &SalesAndProducts,
+    date_format: &TimestampFormat,
+) -> Result<(), String> {
+    let dir = output_dir.as_ref();
+    std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    append_records(dir.join(PRODUCTS_FILE), &data.products)?;
+    write_lines(&dir.join(SALES_FILE), dated(&data.sales, date_format), true)
+}
+
+/// Whether `path` stands for a directory of `.jsonl` files rather than a
+/// single file of tagged records: an existing directory, or a path ending
+/// in a separator, such as `out/`, for a directory still to be created.
+pub fn is_dir_path<P: AsRef<Path>>(path: P) -> bool {
+    let path = path.as_ref();
+    path.is_dir() || path.as_os_str().to_string_lossy().ends_with(std::path::is_separator)
+}
+
+/// Iterates over the records of a tagged file or a directory.
+pub fn stream<P: AsRef<Path>>(
+    input_path: P,
+) -> Result<Box<dyn Iterator<Item = Result<Record, String>>>, String> {
+    let path = input_path.as_ref().to_path_buf();
+    if is_dir_path(&path) {
+        Ok(Box::new(dir_records(path)?))
+    } else {
+        Ok(Box::new(tagged_records(path)?))
+    }
+}
+
+/// Reads a tagged file or a directory.
+pub fn read<S: DeserializeOwned, P: AsRef<Path>>(input_path: P) -> Result<SalesAndProducts<S>, String> {
+    if is_dir_path(&input_path) {
+        read_jsonl_dir(input_path)
+    } else {
+        read_jsonl(input_path)
+    }
+}
+
+/// Writes a tagged file or a directory.
+pub fn write<P: AsRef<Path>>(
+    output_path: P,
+    data: &SalesAndProducts,
+    date_format: &TimestampFormat,
+) -> Result<(), String> {
+    if is_dir_path(&output_path) {
+        write_jsonl_dir(output_path, data, date_format)
+    } else {
+        write_jsonl(output_path, data, date_format)
+    }
+}
+
+/// Appends to a tagged file or a directory.
+pub fn append<P: AsRef<Path>>(
+    output_path: P,
+    data: &SalesAndProducts,
+    date_format: &TimestampFormat,
+) -> Result<(), String> {
+    if is_dir_path(&output_path) {
+        append_jsonl_dir(output_path, data, date_format)
+    } else {
+        append_jsonl(output_path, data, date_format)
+    }
+}
@@ -0,0 +1,218 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize, Serializer};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+pub mod config;
+pub mod csv;
+pub mod diff;
+pub mod format;
+pub mod jsonl;
+pub mod load;
+pub mod quantity;
+pub mod report;
+pub mod store;
+pub mod stream;
+pub mod timestamp;
+pub mod unit;
+pub mod validate;
+pub mod xml;
+
+pub use rust_decimal::Decimal;
+
+pub use diff::{diff, DatasetDiff};
+pub use format::{read_any, Format};
+pub use load::{LoadMode, LoadReport};
+pub use quantity::{QuantityRules, Rounding};
+pub use report::{GroupBy, Report, ReportFormat};
+pub use stream::{json_records, JsonRecords, Record};
+pub use timestamp::{DateRange, Timestamp, TimestampFormat, WithFormat};
+pub use unit::{Dimension, Unit};
+pub use validate::{validate, ValidationReport};
+pub use xml::{read_xml, write_xml, xml_records, XmlRecords};
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct Product {
+    pub id: i32,
+    pub category: String,
+    pub name: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct Sale {
+    pub id: String,
+    pub product_id: i32,
+    pub date: Timestamp,
+    #[serde(with = "quantity")]
+    pub quantity: Decimal,
+    pub unit: Unit,
+}
+
+/// Products and sales, with sales of another type laid out like [`Sale`]
+/// read by the same readers, such as [`validate::RawSale`].
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct SalesAndProducts<S = Sale> {
+    pub products: Vec<Product>,
+    pub sales: Vec<S>,
+}
+
+impl Product {
+    pub fn new(id: i32, category: &str, name: &str) -> Self {
+        Product {
+            id,
+            category: category.to_string(),
+            name: name.to_string(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.category.trim().is_empty() {
+            return Err(format!("product {} has an empty category", self.id));
+        }
+        if self.name.trim().is_empty() {
+            return Err(format!("product {} has an empty name", self.id));
+        }
+        Ok(())
+    }
+}
+
+impl Sale {
+    pub fn new(id: &str, product_id: i32, date: Timestamp, quantity: Decimal, unit: Unit) -> Self {
+        Sale {
+            id: id.to_string(),
+            product_id,
+            date,
+            quantity,
+            unit,
+        }
+    }
+
+    /// The quantity converted to `unit`, which must measure the same
+    /// dimension as the sale unit.
+    pub fn quantity_in(&self, unit: Unit) -> Result<Decimal, String> {
+        self.unit
+            .convert(self.quantity, unit)
+            .map_err(|e| format!("sale {}: {}", self.id, e))
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.id.trim().is_empty() {
+            return Err("sale with an empty id".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl<S> SalesAndProducts<S> {
+    pub fn new(products: Vec<Product>, sales: Vec<S>) -> Self {
+        SalesAndProducts { products, sales }
+    }
+}
+
+impl SalesAndProducts {
+    pub fn product(&self, id: i32) -> Option<&Product> {
+        self.products.iter().find(|product| product.id == id)
+    }
+
+    /// Rounds every sale quantity to the scale of its unit, returning how
+    /// many changed value.
+    pub fn round_quantities(&mut self, rules: &QuantityRules) -> usize {
+        let mut changed = 0;
+        for sale in self.sales.iter_mut() {
+            let rounded = rules.round(sale.quantity, sale.unit);
+            if rounded != sale.quantity {
+                changed += 1;
+            }
+            sale.quantity = rounded;
+        }
+        changed
+    }
+
+    /// Keeps only the sales dated within `range`.
+    pub fn retain_dates(&mut self, range: &DateRange) {
+        self.sales.retain(|sale| range.contains(sale.date));
+    }
+
+    pub fn validate(&self) -> ValidationReport {
+        validate::validate(self)
+    }
+}
+
+// Laid out like `Sale` and `SalesAndProducts`, with the dates in a format
+#[derive(Serialize)]
+#[serde(rename = "Sale")]
+struct DatedSale<'a> {
+    id: &'a str,
+    product_id: i32,
+    date: WithFormat<'a, Timestamp>,
+    #[serde(serialize_with = "quantity::serialize")]
+    quantity: Decimal,
+    unit: Unit,
+}
+
+#[derive(Serialize)]
+#[serde(rename = "SalesAndProducts")]
+struct DatedSalesAndProducts<'a> {
+    products: &'a [Product],
+    sales: WithFormat<'a, [Sale]>,
+}
+
+impl Serialize for WithFormat<'_, Sale> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let sale = self.value;
+        DatedSale {
+            id: &sale.id,
+            product_id: sale.product_id,
+            date: WithFormat::new(&sale.date, self.format),
+            quantity: sale.quantity,
+            unit: sale.unit,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl Serialize for WithFormat<'_, [Sale]> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.value.iter().map(|sale| WithFormat::new(sale, self.format)))
+    }
+}
+
+impl Serialize for WithFormat<'_, SalesAndProducts> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        DatedSalesAndProducts {
+            products: &self.value.products,
+            sales: WithFormat::new(&self.value.sales, self.format),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<S> From<(Vec<Product>, Vec<S>)> for SalesAndProducts<S> {
+    fn from((products, sales): (Vec<Product>, Vec<S>)) -> Self {
+        SalesAndProducts { products, sales }
+    }
+}
+
+impl From<SalesAndProducts> for (Vec<Product>, Vec<Sale>) {
+    fn from(data: SalesAndProducts) -> Self {
+        (data.products, data.sales)
+    }
+}
+
+pub fn read_json<S: DeserializeOwned, P: AsRef<Path>>(input_path: P) -> Result<SalesAndProducts<S>, String> {
+    let file = File::open(input_path).map_err(|e| e.to_string())?;
+    let reader = BufReader::new(file);
+    let json: SalesAndProducts<S> = serde_json::from_reader(reader).map_err(|e| e.to_string())?;
+    Ok(json)
+}
+
+pub fn write_json<P: AsRef<Path>>(
+    output_path: P,
+    data: &SalesAndProducts,
+    date_format: &TimestampFormat,
+) -> Result<(), String> {
+    let file = File::create(output_path).map_err(|e| e.to_string())?;
+    let writer = BufWriter::new(file);
+    serde_json::to_writer_pretty(writer, &WithFormat::new(data, date_format)).map_err(|e| e.to_string())
+}
@@ -0,0 +1,93 @@
+use std::fmt;
+use std::ops::AddAssign;
+use std::str::FromStr;
+
+/// How rows are written when loading data into a database.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LoadMode {
+    /// Plain inserts, failing on rows that already exist.
+    #[default]
+    Insert,
+    /// Inserts new rows and updates the ones that changed.
+    Upsert,
+    /// Upsert, then delete the rows missing from the input.
+    Sync,
+}
+
+impl FromStr for LoadMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "insert" => Ok(LoadMode::Insert),
+            "upsert" => Ok(LoadMode::Upsert),
+            "sync" => Ok(LoadMode::Sync),
+            _ => Err(format!("unknown load mode `{}`, expected one of: insert, upsert, sync", s)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RowChange {
+    #[default]
+    Inserted,
+    Updated,
+    Unchanged,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TableStats {
+    pub inserted: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+    pub deleted: usize,
+}
+
+impl TableStats {
+    pub fn record(&mut self, change: RowChange) {
+        match change {
+            RowChange::Inserted => self.inserted += 1,
+            RowChange::Updated => self.updated += 1,
+            RowChange::Unchanged => self.unchanged += 1,
+        }
+    }
+}
+
+impl AddAssign for TableStats {
+    fn add_assign(&mut self, other: TableStats) {
+        self.inserted += other.inserted;
+        self.updated += other.updated;
+        self.unchanged += other.unchanged;
+        self.deleted += other.deleted;
+    }
+}
+
+impl fmt::Display for TableStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} inserted, {} updated, {} unchanged, {} deleted",
+            self.inserted, self.updated, self.unchanged, self.deleted
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LoadReport {
+    pub products: TableStats,
+    pub sales: TableStats,
+}
+
+impl AddAssign for LoadReport {
+    fn add_assign(&mut self, other: LoadReport) {
+        self.products += other.products;
+        self.sales += other.sales;
+    }
+}
+
+impl fmt::Display for LoadReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Products: {}", self.products)?;
+        write!(f, "Sales: {}", self.sales)
+    }
+}
@@ -0,0 +1,268 @@
+use crate::Unit;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::de::{self, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+/// Most decimal places a quantity can have.
+pub const MAX_SCALE: u32 = 28;
+
+/// How quantities are rounded to the scale of their unit.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Rounding {
+    /// Halves to the even neighbour, as in 2.125 to 2.12, to avoid biasing
+    /// totals
+    #[default]
+    HalfEven,
+    /// Halves away from zero, as in 2.125 to 2.13
+    HalfUp,
+    /// Halves toward zero, as in 2.125 to 2.12
+    HalfDown,
+    /// Away from zero
+    Up,
+    /// Toward zero, truncating
+    Down,
+}
+
+impl Rounding {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Rounding::HalfEven => "half-even",
+            Rounding::HalfUp => "half-up",
+            Rounding::HalfDown => "half-down",
+            Rounding::Up => "up",
+            Rounding::Down => "down",
+        }
+    }
+
+    fn strategy(&self) -> RoundingStrategy {
+        match self {
+            Rounding::HalfEven => RoundingStrategy::MidpointNearestEven,
+            Rounding::HalfUp => RoundingStrategy::MidpointAwayFromZero,
+            Rounding::HalfDown => RoundingStrategy::MidpointTowardZero,
+            Rounding::Up => RoundingStrategy::AwayFromZero,
+            Rounding::Down => RoundingStrategy::ToZero,
+        }
+    }
+}
+
+impl fmt::Display for Rounding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Rounding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "half-even" => Ok(Rounding::HalfEven),
+            "half-up" => Ok(Rounding::HalfUp),
+            "half-down" => Ok(Rounding::HalfDown),
+            "up" => Ok(Rounding::Up),
+            "down" => Ok(Rounding::Down),
+            _ => Err(format!(
+                "unknown rounding `{}`, expected one of: half-even, half-up, half-down, up, down",
+                s
+            )),
+        }
+    }
+}
+
+/// Decimal places kept for the quantities of each unit, and how the rest
+/// is rounded off. This is the `[quantity]` section of the config.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct QuantityRules {
+    pub rounding: Rounding,
+    pub piece_scale: u32,
+    pub kilogram_scale: u32,
+    pub gram_scale: u32,
+    pub pound_scale: u32,
+    pub litre_scale: u32,
+    pub millilitre_scale: u32,
+}
+
+impl Default for QuantityRules {
+    fn default() -> Self {
+        QuantityRules {
+            rounding: Rounding::HalfEven,
+            piece_scale: 0,
+            kilogram_scale: 3,
+            gram_scale: 0,
+            pound_scale: 3,
+            litre_scale: 3,
+            millilitre_scale: 0,
+        }
+    }
+}
+
+impl QuantityRules {
+    pub fn scale(&self, unit: Unit) -> u32 {
+        let scale = match unit {
+            Unit::Piece => self.piece_scale,
+            Unit::Kilogram => self.kilogram_scale,
+            Unit::Gram => self.gram_scale,
+            Unit::Pound => self.pound_scale,
+            Unit::Litre => self.litre_scale,
+            Unit::Millilitre => self.millilitre_scale,
+        };
+        scale.min(MAX_SCALE)
+    }
+
+    /// `quantity` of `unit` rounded to exactly the scale of the unit.
+    pub fn round(&self, quantity: Decimal, unit: Unit) -> Decimal {
+        let scale = self.scale(unit);
+        let mut rounded = quantity.round_dp_with_strategy(scale, self.rounding.strategy());
+        rounded.rescale(scale);
+        rounded
+    }
+}
+
+/// The decimal written the same as the shortest form of `value`, so `2.14`
+/// is 2.14 rather than the binary float closest to it.
+pub fn from_f64(value: f64) -> Option<Decimal> {
+    if !value.is_finite() {
+        return None;
+    }
+    let text = value.to_string();
+    Decimal::from_str(&text)
+        .or_else(|_| Decimal::from_scientific(&text))
+        .ok()
+}
+
+/// The float standing for `quantity`, if it reads back as the same value.
+pub fn to_f64(quantity: Decimal) -> Option<f64> {
+    quantity
+        .to_f64()
+        .filter(|float| from_f64(*float).is_some_and(|decimal| decimal == quantity))
+}
+
+pub fn parse(s: &str) -> Result<Decimal, String> {
+    let s = s.trim();
+    Decimal::from_str(s)
+        .or_else(|_| Decimal::from_scientific(s))
+        .map_err(|_| format!("invalid quantity `{}`", s))
+}
+
+// JSON and TOML only have binary floats, so a quantity is written as the
+// float whose shortest form reads back as the same decimal, or as a string
+// when no float does
+pub fn serialize<S: Serializer>(quantity: &Decimal, serializer: S) -> Result<S::Ok, S::Error> {
+    match to_f64(*quantity) {
+        Some(float) => serializer.serialize_f64(float),
+        None => serializer.serialize_str(&quantity.to_string()),
+    }
+}
+
+// The name of serde_json's `RawValue`, whose text serde_json hands over as
+// is, so JSON numbers are read without going through a float
+const RAW_VALUE: &str = "$serde_json::private::RawValue";
+
+/// Reads integers and decimal strings, and numbers from their text in JSON,
+/// CSV and XML, for `#[serde(with = "crate::quantity")]`. Floats of other
+/// formats, such as TOML, are read through their shortest form.
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Decimal, D::Error> {
+    deserializer.deserialize_newtype_struct(RAW_VALUE, QuantityVisitor)
+}
+
+struct QuantityVisitor;
+
+impl<'de> Visitor<'de> for QuantityVisitor {
+    type Value = Decimal;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a decimal number")
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Decimal, E> {
+        Ok(Decimal::from(value))
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Decimal, E> {
+        Ok(Decimal::from(value))
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<Decimal, E> {
+        from_f64(value).ok_or_else(|| E::custom(format!("quantity {} is out of range", value)))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Decimal, E> {
+        parse(value).map_err(E::custom)
+    }
+
+    // Other formats ignore the name: CSV fields and XML text are read as
+    // written, and TOML gives its numbers
+    fn visit_newtype_struct<D: Deserializer<'de>>(self, deserializer: D) -> Result<Decimal, D::Error> {
+        deserializer.deserialize_str(QuantityVisitor)
+    }
+
+    // The JSON text of a number or string
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Decimal, A::Error> {
+        let text = match map.next_entry::<String, String>()? {
+            Some((_, text)) => text,
+            None => return Err(de::Error::custom("missing quantity")),
+        };
+        if text.starts_with('"') {
+            let text: String = serde_json::from_str(&text).map_err(de::Error::custom)?;
+            return parse(&text).map_err(de::Error::custom);
+        }
+        parse(&text).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Record, Sale};
+
+    const DIGITS: &str = "1.2345678901234567890123";
+
+    fn sale_json(quantity: &str) -> String {
+        format!(r#"{{"id": "a", "product_id": 1, "date": 0, "quantity": {}, "unit": "Kg"}}"#, quantity)
+    }
+
+    #[test]
+    fn json_numbers_keep_every_digit() {
+        let exact = Decimal::from_str(DIGITS).unwrap();
+        let sale: Sale = serde_json::from_str(&sale_json(DIGITS)).unwrap();
+        assert_eq!(sale.quantity, exact);
+        assert_eq!(sale.quantity.to_string(), DIGITS);
+
+        let sale: Sale = serde_json::from_str(&sale_json(&format!("\"{}\"", DIGITS))).unwrap();
+        assert_eq!(sale.quantity, exact);
+
+        let line = format!(r#"{{"type": "sale", {}"#, &sale_json(DIGITS)[1..]);
+        match serde_json::from_str::<Record>(&line).unwrap() {
+            Record::Sale(sale) => assert_eq!(sale.quantity, exact),
+            record => panic!("expected a sale, found {:?}", record),
+        }
+
+        let sale: Sale = serde_json::from_str(&sale_json("1.5e-3")).unwrap();
+        assert_eq!(sale.quantity.to_string(), "0.0015");
+        assert!(serde_json::from_str::<Sale>(&sale_json("null")).is_err());
+    }
+
+    #[test]
+    fn csv_fields_keep_every_digit() {
+        let content = format!("id,product_id,date,quantity,unit\na,1,0,{},Kg\nb,1,0,2.50,Kg\n", DIGITS);
+        let sales: Vec<Sale> = ::csv::Reader::from_reader(content.as_bytes())
+            .deserialize()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(sales[0].quantity.to_string(), DIGITS);
+        assert_eq!(sales[1].quantity.to_string(), "2.50");
+    }
+
+    #[test]
+    fn quantities_too_precise_for_a_float_are_written_as_strings() {
+        let sale: Sale = serde_json::from_str(&sale_json(DIGITS)).unwrap();
+        let json = serde_json::to_string(&sale).unwrap();
+        assert!(json.contains(&format!(r#""quantity":"{}""#, DIGITS)), "{}", json);
+        let sale: Sale = serde_json::from_str(&sale_json("2.14")).unwrap();
+        assert!(serde_json::to_string(&sale).unwrap().contains(r#""quantity":2.14"#));
+    }
+}
@@ -0,0 +1,327 @@
+use crate::{quantity, Decimal, Product, QuantityRules, Sale, SalesAndProducts};
+use chrono::Datelike;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
+
+const UNKNOWN: &str = "(unknown)";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GroupBy {
+    Product,
+    Category,
+    Unit,
+    Day,
+    /// ISO 8601 week, as in `2009-W07`
+    Week,
+    Month,
+}
+
+impl GroupBy {
+    pub fn name(&self) -> &'static str {
+        match self {
+            GroupBy::Product => "product",
+            GroupBy::Category => "category",
+            GroupBy::Unit => "unit",
+            GroupBy::Day => "day",
+            GroupBy::Week => "week",
+            GroupBy::Month => "month",
+        }
+    }
+
+    fn key(&self, sale: &Sale, products: &HashMap<i32, &Product>) -> Key {
+        let product = products.get(&sale.product_id);
+        let key = match self {
+            GroupBy::Product => {
+                let name = product.map_or(UNKNOWN, |product| product.name.as_str());
+                return Key::Product(sale.product_id, name.to_string());
+            }
+            GroupBy::Category => product.map_or(UNKNOWN.to_string(), |product| product.category.clone()),
+            GroupBy::Unit => sale.unit.dimension().base_unit().to_string(),
+            // Periods are calendar days in UTC
+            period => {
+                let date = sale.date.datetime();
+                match period {
+                    GroupBy::Day => date.format("%Y-%m-%d").to_string(),
+                    GroupBy::Week => {
+                        let week = date.iso_week();
+                        format!("{}-W{:02}", week.year(), week.week())
+                    }
+                    _ => date.format("%Y-%m").to_string(),
+                }
+            }
+        };
+        Key::Text(key)
+    }
+}
+
+/// A grouping value, ordering products by id so that product 9 comes
+/// before product 10.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Key {
+    Product(i32, String),
+    Text(String),
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Key::Product(id, name) => write!(f, "{} {}", id, name),
+            Key::Text(text) => f.write_str(text),
+        }
+    }
+}
+
+impl fmt::Display for GroupBy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for GroupBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "product" => Ok(GroupBy::Product),
+            "category" => Ok(GroupBy::Category),
+            "unit" => Ok(GroupBy::Unit),
+            "day" => Ok(GroupBy::Day),
+            "week" => Ok(GroupBy::Week),
+            "month" => Ok(GroupBy::Month),
+            _ => Err(format!(
+                "unknown grouping `{}`, expected one of: product, category, unit, day, week, month",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReportFormat {
+    Text,
+    Csv,
+    Json,
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(ReportFormat::Text),
+            "csv" => Ok(ReportFormat::Csv),
+            "json" => Ok(ReportFormat::Json),
+            _ => Err(format!("unknown report format `{}`, expected text, csv or json", s)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReportRow {
+    /// One value per grouping column
+    pub keys: Vec<String>,
+    pub sales: usize,
+    pub quantity: Decimal,
+}
+
+/// Totals of sale quantities per group, sorted by group.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Report {
+    pub group_by: Vec<GroupBy>,
+    pub rows: Vec<ReportRow>,
+}
+
+impl Report {
+    /// Groups the sales by `group_by`, joined to their products. Quantities
+    /// are converted to the base unit of their dimension (`Kg`, `L` or `u.`)
+    /// and only added up within a dimension, so the unit is always part of
+    /// the grouping. Each converted quantity is rounded to the scale of the
+    /// base unit before it is added, so totals are the sum of the rounded
+    /// amounts.
+    pub fn new(data: &SalesAndProducts, group_by: &[GroupBy], rules: &QuantityRules) -> Result<Report, String> {
+        let mut group_by = group_by.to_vec();
+        if !group_by.contains(&GroupBy::Unit) {
+            group_by.push(GroupBy::Unit);
+        }
+
+        // Reversed so the first product with an id wins, as in `SalesAndProducts::product`
+        let products: HashMap<i32, &Product> = data.products.iter().rev().map(|product| (product.id, product)).collect();
+        let mut totals: BTreeMap<Vec<Key>, (usize, Decimal)> = BTreeMap::new();
+        for sale in data.sales.iter() {
+            let keys = group_by.iter().map(|group| group.key(sale, &products)).collect();
+            let unit = sale.unit.dimension().base_unit();
+            let quantity = rules.round(sale.quantity_in(unit)?, unit);
+            let total = totals
+                .entry(keys)
+                .or_insert_with(|| (0, rules.round(Decimal::ZERO, unit)));
+            total.0 += 1;
+            total.1 = total
+                .1
+                .checked_add(quantity)
+                .ok_or_else(|| format!("total quantity overflows adding sale {}", sale.id))?;
+        }
+
+        let rows = totals
+            .into_iter()
+            .map(|(keys, (sales, quantity))| ReportRow {
+                keys: keys.iter().map(Key::to_string).collect(),
+                sales,
+                quantity,
+            })
+            .collect();
+        Ok(Report { group_by, rows })
+    }
+
+    pub fn columns(&self) -> Vec<String> {
+        let mut columns: Vec<String> = self.group_by.iter().map(|group| group.name().to_string()).collect();
+        columns.push("sales".to_string());
+        columns.push("quantity".to_string());
+        columns
+    }
+
+    pub fn render(&self, format: ReportFormat) -> Result<String, String> {
+        match format {
+            ReportFormat::Text => Ok(self.to_string()),
+            ReportFormat::Csv => self.to_csv(),
+            ReportFormat::Json => serde_json::to_string_pretty(&self.to_json()).map_err(|e| e.to_string()),
+        }
+    }
+
+    fn cells(&self, row: &ReportRow) -> Vec<String> {
+        let mut cells = row.keys.clone();
+        cells.push(row.sales.to_string());
+        cells.push(row.quantity.to_string());
+        cells
+    }
+
+    pub fn to_csv(&self) -> Result<String, String> {
+        let mut writer = ::csv::Writer::from_writer(vec![]);
+        writer.write_record(self.columns()).map_err(|e| e.to_string())?;
+        for row in self.rows.iter() {
+            writer.write_record(self.cells(row)).map_err(|e| e.to_string())?;
+        }
+        let bytes = writer.into_inner().map_err(|e| e.to_string())?;
+        String::from_utf8(bytes).map_err(|e| e.to_string())
+    }
+
+    /// An array with one object per row, keyed by column.
+    pub fn to_json(&self) -> Value {
+        let rows = self
+            .rows
+            .iter()
+            .map(|row| {
+                let mut object = Map::new();
+                for (group, key) in self.group_by.iter().zip(row.keys.iter()) {
+                    object.insert(group.name().to_string(), Value::from(key.as_str()));
+                }
+                object.insert("sales".to_string(), Value::from(row.sales));
+                // Totals too precise for a JSON number are written as strings
+                let total = match quantity::to_f64(row.quantity) {
+                    Some(total) => Value::from(total),
+                    None => Value::from(row.quantity.to_string()),
+                };
+                object.insert("quantity".to_string(), total);
+                Value::Object(object)
+            })
+            .collect();
+        Value::Array(rows)
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let columns = self.columns();
+        let rows: Vec<Vec<String>> = self.rows.iter().map(|row| self.cells(row)).collect();
+        let widths: Vec<usize> = (0..columns.len())
+            .map(|i| {
+                rows.iter()
+                    .map(|cells| cells[i].chars().count())
+                    .chain([columns[i].len()])
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+        // Totals are right aligned, the grouping columns left aligned
+        let numeric = columns.len() - 2;
+
+        let line = |cells: &[String]| {
+            cells
+                .iter()
+                .enumerate()
+                .map(|(i, cell)| {
+                    if i >= numeric {
+                        format!("{:>width$}", cell, width = widths[i])
+                    } else {
+                        format!("{:<width$}", cell, width = widths[i])
+                    }
+                })
+                .collect::<Vec<String>>()
+                .join("  ")
+                .trim_end()
+                .to_string()
+        };
+
+        writeln!(f, "{}", line(&columns))?;
+        for cells in rows.iter() {
+            writeln!(f, "{}", line(cells))?;
+        }
+        write!(f, "{} groups", self.rows.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Timestamp, Unit};
+
+    fn sale(id: &str, product_id: i32, quantity: i64, unit: Unit) -> Sale {
+        Sale::new(id, product_id, Timestamp::default(), Decimal::new(quantity, 0), unit)
+    }
+
+    fn keys(report: &Report) -> Vec<Vec<&str>> {
+        report
+            .rows
+            .iter()
+            .map(|row| row.keys.iter().map(String::as_str).collect())
+            .collect()
+    }
+
+    #[test]
+    fn products_are_sorted_by_id() {
+        let data = SalesAndProducts::new(
+            vec![Product::new(10, "fruit", "apple"), Product::new(9, "fruit", "pear"), Product::new(100, "tools", "axe")],
+            vec![
+                sale("a", 100, 1, Unit::Piece),
+                sale("b", 10, 1, Unit::Piece),
+                sale("c", 9, 2, Unit::Piece),
+                sale("d", 10, 3, Unit::Piece),
+                sale("e", 2, 1, Unit::Piece),
+            ],
+        );
+        let report = Report::new(&data, &[GroupBy::Product], &QuantityRules::default()).unwrap();
+        assert_eq!(
+            keys(&report),
+            [
+                ["2 (unknown)", "u."],
+                ["9 pear", "u."],
+                ["10 apple", "u."],
+                ["100 axe", "u."],
+            ]
+        );
+        assert_eq!(report.rows[2].sales, 2);
+        assert_eq!(report.rows[2].quantity, Decimal::new(4, 0));
+    }
+
+    #[test]
+    fn categories_come_from_the_first_product_with_the_id() {
+        let data = SalesAndProducts::new(
+            vec![Product::new(1, "fruit", "apple"), Product::new(1, "tools", "axe")],
+            vec![sale("a", 1, 1, Unit::Kilogram), sale("b", 1, 500, Unit::Gram), sale("c", 3, 1, Unit::Litre)],
+        );
+        let report = Report::new(&data, &[GroupBy::Category, GroupBy::Unit], &QuantityRules::default()).unwrap();
+        assert_eq!(keys(&report), [["(unknown)", "L"], ["fruit", "Kg"]]);
+        assert_eq!(report.rows[1].quantity.to_string(), "1.500");
+    }
+}
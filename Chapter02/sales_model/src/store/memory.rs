@@ -0,0 +1,148 @@
+use super::{Migration, SalesStore};
+use crate::load::RowChange;
+use crate::{DateRange, Product, Sale};
+use std::collections::{BTreeMap, HashSet};
+
+/// A store kept in memory, for tests and dry runs. It enforces the same
+/// primary and foreign keys as the database schemas.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryStore {
+    products: BTreeMap<i32, Product>,
+    sales: BTreeMap<String, Sale>,
+    snapshot: Option<(BTreeMap<i32, Product>, BTreeMap<String, Sale>)>,
+    migrations: &'static [Migration],
+    version: u32,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        MemoryStore::default()
+    }
+
+    /// A store reporting `migrations` as its schema history. Applying them
+    /// only records the schema version, as there is no schema to change.
+    pub fn with_migrations(migrations: &'static [Migration]) -> Self {
+        MemoryStore {
+            migrations,
+            ..MemoryStore::default()
+        }
+    }
+
+    fn check_product_exists(&self, sale: &Sale) -> Result<(), String> {
+        if self.products.contains_key(&sale.product_id) {
+            Ok(())
+        } else {
+            Err(format!(
+                "sale {} refers to missing product {}",
+                sale.id, sale.product_id
+            ))
+        }
+    }
+}
+
+impl SalesStore for MemoryStore {
+    fn migrations(&self) -> &'static [Migration] {
+        self.migrations
+    }
+
+    fn schema_version(&mut self) -> Result<u32, String> {
+        Ok(self.version)
+    }
+
+    fn apply_migration(&mut self, migration: &Migration, up: bool) -> Result<(), String> {
+        self.version = if up {
+            migration.version
+        } else {
+            self.migrations
+                .iter()
+                .map(|previous| previous.version)
+                .filter(|version| *version < migration.version)
+                .max()
+                .unwrap_or(0)
+        };
+        Ok(())
+    }
+
+    fn begin(&mut self) -> Result<(), String> {
+        if self.snapshot.is_some() {
+            return Err("a transaction is already open".to_string());
+        }
+        self.snapshot = Some((self.products.clone(), self.sales.clone()));
+        Ok(())
+    }
+
+    fn commit(&mut self) -> Result<(), String> {
+        self.snapshot.take().map(|_| ()).ok_or_else(|| "no open transaction".to_string())
+    }
+
+    fn rollback(&mut self) -> Result<(), String> {
+        let (products, sales) = self.snapshot.take().ok_or_else(|| "no open transaction".to_string())?;
+        self.products = products;
+        self.sales = sales;
+        Ok(())
+    }
+
+    fn insert_product(&mut self, product: &Product) -> Result<(), String> {
+        if self.products.contains_key(&product.id) {
+            return Err(format!("duplicate product id {}", product.id));
+        }
+        self.products.insert(product.id, product.clone());
+        Ok(())
+    }
+
+    fn insert_sale(&mut self, sale: &Sale) -> Result<(), String> {
+        if self.sales.contains_key(&sale.id) {
+            return Err(format!("duplicate sale id {}", sale.id));
+        }
+        self.check_product_exists(sale)?;
+        self.sales.insert(sale.id.clone(), sale.clone());
+        Ok(())
+    }
+
+    fn upsert_product(&mut self, product: &Product) -> Result<RowChange, String> {
+        let change = match self.products.get(&product.id) {
+            None => RowChange::Inserted,
+            Some(existing) if existing == product => return Ok(RowChange::Unchanged),
+            Some(_) => RowChange::Updated,
+        };
+        self.products.insert(product.id, product.clone());
+        Ok(change)
+    }
+
+    fn upsert_sale(&mut self, sale: &Sale) -> Result<RowChange, String> {
+        self.check_product_exists(sale)?;
+        let change = match self.sales.get(&sale.id) {
+            None => RowChange::Inserted,
+            Some(existing) if existing == sale => return Ok(RowChange::Unchanged),
+            Some(_) => RowChange::Updated,
+        };
+        self.sales.insert(sale.id.clone(), sale.clone());
+        Ok(change)
+    }
+
+    fn delete_products_except(&mut self, keep: &HashSet<i32>) -> Result<usize, String> {
+        let before = self.products.len();
+        self.products.retain(|id, _| keep.contains(id));
+        if let Some(sale) = self.sales.values().find(|sale| !self.products.contains_key(&sale.product_id)) {
+            return Err(format!(
+                "cannot delete product {}, sale {} refers to it",
+                sale.product_id, sale.id
+            ));
+        }
+        Ok(before - self.products.len())
+    }
+
+    fn delete_sales_except(&mut self, keep: &HashSet<String>) -> Result<usize, String> {
+        let before = self.sales.len();
+        self.sales.retain(|id, _| keep.contains(id));
+        Ok(before - self.sales.len())
+    }
+
+    fn products(&mut self) -> Result<Vec<Product>, String> {
+        Ok(self.products.values().cloned().collect())
+    }
+
+    fn sales(&mut self, range: &DateRange) -> Result<Vec<Sale>, String> {
+        Ok(self.sales.values().filter(|sale| range.contains(sale.date)).cloned().collect())
+    }
+}
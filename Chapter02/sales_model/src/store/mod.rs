@@ -0,0 +1,662 @@
+use crate::load::RowChange;
+use crate::stream::Record;
+use crate::{DateRange, Decimal, LoadMode, LoadReport, Product, QuantityRules, Sale, SalesAndProducts};
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+
+mod memory;
+#[cfg(feature = "postgres")]
+mod postgres;
+#[cfg(feature = "redis")]
+mod redis;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+pub use memory::MemoryStore;
+#[cfg(feature = "postgres")]
+pub use self::postgres::PostgresStore;
+#[cfg(feature = "redis")]
+pub use self::redis::RedisStore;
+#[cfg(feature = "sqlite")]
+pub use self::sqlite::SqliteStore;
+
+/// Records written per batch when loading.
+pub const DEFAULT_BATCH_SIZE: usize = 1000;
+
+#[derive(Debug)]
+pub struct Migration {
+    pub version: u32,
+    pub name: &'static str,
+    pub up: &'static str,
+    pub down: &'static str,
+}
+
+/// Storage for products and sales. Backends provide row level operations;
+/// loading, syncing and migrating are built on top of them by the functions
+/// in this module so every backend behaves the same.
+pub trait SalesStore {
+    /// Schema migrations of the backend, ordered by version.
+    fn migrations(&self) -> &'static [Migration];
+
+    fn schema_version(&mut self) -> Result<u32, String>;
+
+    /// Runs a migration, or reverts it when `up` is false, and records the
+    /// resulting schema version.
+    fn apply_migration(&mut self, migration: &Migration, up: bool) -> Result<(), String>;
+
+    fn begin(&mut self) -> Result<(), String>;
+
+    fn commit(&mut self) -> Result<(), String>;
+
+    fn rollback(&mut self) -> Result<(), String>;
+
+    /// Inserts a product, failing if its id already exists.
+    fn insert_product(&mut self, product: &Product) -> Result<(), String>;
+
+    /// Inserts a sale, failing if its id already exists.
+    fn insert_sale(&mut self, sale: &Sale) -> Result<(), String>;
+
+    fn upsert_product(&mut self, product: &Product) -> Result<RowChange, String>;
+
+    fn upsert_sale(&mut self, sale: &Sale) -> Result<RowChange, String>;
+
+    /// Writes a batch of products, then a batch of sales, according to the
+    /// load mode. Backends with a faster path for many rows override this.
+    fn write_batch(&mut self, products: &[Product], sales: &[Sale], mode: LoadMode) -> Result<LoadReport, String> {
+        write_rows(self, products, sales, mode)
+    }
+
+    /// Deletes every product whose id is not in `keep`, returning how many.
+    fn delete_products_except(&mut self, keep: &HashSet<i32>) -> Result<usize, String>;
+
+    /// Deletes every sale whose id is not in `keep`, returning how many.
+    fn delete_sales_except(&mut self, keep: &HashSet<String>) -> Result<usize, String>;
+
+    fn products(&mut self) -> Result<Vec<Product>, String>;
+
+    /// Sales dated within `range`, ordered by id.
+    fn sales(&mut self, range: &DateRange) -> Result<Vec<Sale>, String>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    Sqlite,
+    Postgres,
+    Redis,
+    Memory,
+}
+
+impl Backend {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Backend::Sqlite => "sqlite",
+            Backend::Postgres => "postgres",
+            Backend::Redis => "redis",
+            Backend::Memory => "memory",
+        }
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "sqlite" => Ok(Backend::Sqlite),
+            "postgres" | "postgresql" => Ok(Backend::Postgres),
+            "redis" => Ok(Backend::Redis),
+            "memory" => Ok(Backend::Memory),
+            _ => Err(format!("unknown backend `{}`, expected one of: sqlite, postgres, redis, memory", s)),
+        }
+    }
+}
+
+/// How batches of rows are sent to Postgres. Only inserts can use `COPY`;
+/// upserts always use multi-row INSERT statements, unless `row` is chosen.
+/// Other backends write rows one at a time whatever the choice.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BulkLoad {
+    /// `COPY ... FROM STDIN` in the binary format
+    #[default]
+    Binary,
+    /// `COPY ... FROM STDIN` in the CSV format
+    Csv,
+    /// INSERT statements of many rows each
+    Insert,
+    /// One statement per row
+    Row,
+}
+
+impl BulkLoad {
+    /// Help of the `--bulk-load` option of the binaries that load data.
+    pub const HELP: &'static str = "How Postgres loads each batch: binary or csv COPY, falling back to \
+        insert when COPY is refused, insert for multi-row INSERTs, or row";
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BulkLoad::Binary => "binary",
+            BulkLoad::Csv => "csv",
+            BulkLoad::Insert => "insert",
+            BulkLoad::Row => "row",
+        }
+    }
+}
+
+impl fmt::Display for BulkLoad {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for BulkLoad {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "binary" => Ok(BulkLoad::Binary),
+            "csv" => Ok(BulkLoad::Csv),
+            "insert" => Ok(BulkLoad::Insert),
+            "row" => Ok(BulkLoad::Row),
+            _ => Err(format!("unknown bulk load `{}`, expected one of: binary, csv, insert, row", s)),
+        }
+    }
+}
+
+/// Opens a store. `location` is the database file for SQLite and the
+/// connection URL for Postgres and Redis; it is ignored by the memory
+/// backend.
+#[cfg_attr(
+    not(any(feature = "sqlite", feature = "postgres", feature = "redis")),
+    allow(unused_variables)
+)]
+pub fn open(backend: Backend, location: &str) -> Result<Box<dyn SalesStore>, String> {
+    match backend {
+        #[cfg(feature = "sqlite")]
+        Backend::Sqlite => Ok(Box::new(SqliteStore::open(location)?)),
+        #[cfg(feature = "postgres")]
+        Backend::Postgres => Ok(Box::new(PostgresStore::connect(location)?)),
+        #[cfg(feature = "redis")]
+        Backend::Redis => Ok(Box::new(RedisStore::connect(location)?)),
+        Backend::Memory => Ok(Box::new(MemoryStore::new())),
+        #[allow(unreachable_patterns)]
+        _ => Err(format!("sales_model was built without the {} backend", backend)),
+    }
+}
+
+/// Like [`open`], loading Postgres batches with `bulk_load`.
+#[cfg_attr(not(feature = "postgres"), allow(unused_variables))]
+pub fn open_with(backend: Backend, location: &str, bulk_load: BulkLoad) -> Result<Box<dyn SalesStore>, String> {
+    #[cfg(feature = "postgres")]
+    if backend == Backend::Postgres {
+        let mut store = PostgresStore::connect(location)?;
+        store.set_bulk_load(bulk_load);
+        return Ok(Box::new(store));
+    }
+    open(backend, location)
+}
+
+/// Runs `f` inside a transaction, rolling back if it fails.
+pub fn in_transaction<S, T, F>(store: &mut S, f: F) -> Result<T, String>
+where
+    S: SalesStore + ?Sized,
+    F: FnOnce(&mut S) -> Result<T, String>,
+{
+    store.begin()?;
+    match f(store) {
+        Ok(value) => {
+            store.commit()?;
+            Ok(value)
+        }
+        Err(e) => {
+            // Keep the original error, it explains why the rollback happened
+            let _ = store.rollback();
+            Err(e)
+        }
+    }
+}
+
+/// Writes one product according to the load mode.
+pub fn write_product<S: SalesStore + ?Sized>(
+    store: &mut S,
+    product: &Product,
+    mode: LoadMode,
+) -> Result<RowChange, String> {
+    match mode {
+        LoadMode::Insert => store.insert_product(product).map(|_| RowChange::Inserted),
+        LoadMode::Upsert | LoadMode::Sync => store.upsert_product(product),
+    }
+}
+
+/// Writes one sale according to the load mode.
+pub fn write_sale<S: SalesStore + ?Sized>(store: &mut S, sale: &Sale, mode: LoadMode) -> Result<RowChange, String> {
+    match mode {
+        LoadMode::Insert => store.insert_sale(sale).map(|_| RowChange::Inserted),
+        LoadMode::Upsert | LoadMode::Sync => store.upsert_sale(sale),
+    }
+}
+
+/// Writes products and then sales one row at a time, the default
+/// [`SalesStore::write_batch`].
+pub fn write_rows<S: SalesStore + ?Sized>(
+    store: &mut S,
+    products: &[Product],
+    sales: &[Sale],
+    mode: LoadMode,
+) -> Result<LoadReport, String> {
+    let mut report = LoadReport::default();
+    for product in products.iter() {
+        report.products.record(write_product(store, product, mode)?);
+    }
+    for sale in sales.iter() {
+        report.sales.record(write_sale(store, sale, mode)?);
+    }
+    Ok(report)
+}
+
+/// Loads products and sales in a single transaction.
+pub fn load<S: SalesStore + ?Sized>(
+    store: &mut S,
+    data: &SalesAndProducts,
+    mode: LoadMode,
+) -> Result<LoadReport, String> {
+    in_transaction(store, |store| {
+        let mut writer = BatchWriter::new(store, mode, DEFAULT_BATCH_SIZE);
+        for product in data.products.iter() {
+            writer.push_product(product.clone())?;
+        }
+        for sale in data.sales.iter() {
+            writer.push_sale(sale.clone())?;
+        }
+        writer.finish()
+    })
+}
+
+/// Checks each record of a stream, rounds sale quantities to the scale of
+/// their unit and writes the records in batches, all in a single
+/// transaction. Returns the load report and how many quantities changed when
+/// rounded. Checks across records, such as duplicate ids and references to
+/// missing products, are left to the store.
+pub fn load_records<S, I>(
+    store: &mut S,
+    records: I,
+    rules: &QuantityRules,
+    mode: LoadMode,
+    batch_size: usize,
+) -> Result<(LoadReport, usize), String>
+where
+    S: SalesStore + ?Sized,
+    I: IntoIterator<Item = Result<Record, String>>,
+{
+    in_transaction(store, |store| {
+        let mut writer = BatchWriter::new(store, mode, batch_size);
+        let mut rounded = 0;
+
+        for record in records {
+            match record? {
+                Record::Product(product) => {
+                    product.validate()?;
+                    writer.push_product(product)?;
+                }
+                Record::Sale(mut sale) => {
+                    sale.validate()?;
+                    if sale.quantity < Decimal::ZERO {
+                        return Err(format!("sale {}: quantity {} is negative", sale.id, sale.quantity));
+                    }
+                    let quantity = rules.round(sale.quantity, sale.unit);
+                    if quantity != sale.quantity {
+                        rounded += 1;
+                    }
+                    sale.quantity = quantity;
+                    writer.push_sale(sale)?;
+                }
+            }
+        }
+
+        Ok((writer.finish()?, rounded))
+    })
+}
+
+/// Writes records to a store in batches of a bounded size, so loading a
+/// stream of records does not hold all of them in memory. Only the ids are
+/// kept in sync mode, to delete the rows missing from the input at the end.
+/// Call [`BatchWriter::finish`] inside a transaction.
+pub struct BatchWriter<'a, S: SalesStore + ?Sized> {
+    store: &'a mut S,
+    mode: LoadMode,
+    size: usize,
+    products: Vec<Product>,
+    sales: Vec<Sale>,
+    product_ids: HashSet<i32>,
+    sale_ids: HashSet<String>,
+    report: LoadReport,
+}
+
+impl<'a, S: SalesStore + ?Sized> BatchWriter<'a, S> {
+    pub fn new(store: &'a mut S, mode: LoadMode, size: usize) -> Self {
+        BatchWriter {
+            store,
+            mode,
+            size: size.max(1),
+            products: vec![],
+            sales: vec![],
+            product_ids: HashSet::new(),
+            sale_ids: HashSet::new(),
+            report: LoadReport::default(),
+        }
+    }
+
+    pub fn push(&mut self, record: Record) -> Result<(), String> {
+        match record {
+            Record::Product(product) => self.push_product(product),
+            Record::Sale(sale) => self.push_sale(sale),
+        }
+    }
+
+    pub fn push_product(&mut self, product: Product) -> Result<(), String> {
+        if self.mode == LoadMode::Sync {
+            self.product_ids.insert(product.id);
+        }
+        self.products.push(product);
+        self.flush_full()
+    }
+
+    pub fn push_sale(&mut self, sale: Sale) -> Result<(), String> {
+        if self.mode == LoadMode::Sync {
+            self.sale_ids.insert(sale.id.clone());
+        }
+        self.sales.push(sale);
+        self.flush_full()
+    }
+
+    fn flush_full(&mut self) -> Result<(), String> {
+        if self.products.len() + self.sales.len() >= self.size {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Writes the records pushed since the last flush.
+    pub fn flush(&mut self) -> Result<(), String> {
+        if self.products.is_empty() && self.sales.is_empty() {
+            return Ok(());
+        }
+        self.report += self.store.write_batch(&self.products, &self.sales, self.mode)?;
+        self.products.clear();
+        self.sales.clear();
+        Ok(())
+    }
+
+    /// Writes the remaining records and, in sync mode, deletes the rows that
+    /// were not pushed.
+    pub fn finish(mut self) -> Result<LoadReport, String> {
+        self.flush()?;
+        if self.mode == LoadMode::Sync {
+            self.report.sales.deleted = self.store.delete_sales_except(&self.sale_ids)?;
+            self.report.products.deleted = self.store.delete_products_except(&self.product_ids)?;
+        }
+        Ok(self.report)
+    }
+}
+
+/// All products, and the sales dated within `range`.
+pub fn query<S: SalesStore + ?Sized>(store: &mut S, range: &DateRange) -> Result<SalesAndProducts, String> {
+    let products = store.products()?;
+    let sales = store.sales(range)?;
+    Ok(SalesAndProducts::new(products, sales))
+}
+
+/// Applies pending migrations up to `target`, or all of them.
+pub fn migrate_up<S: SalesStore + ?Sized>(
+    store: &mut S,
+    target: Option<u32>,
+) -> Result<Vec<&'static Migration>, String> {
+    let current = store.schema_version()?;
+    let target = target.unwrap_or(u32::MAX);
+    let mut applied = vec![];
+
+    for migration in store.migrations().iter() {
+        if migration.version <= current || migration.version > target {
+            continue;
+        }
+        store.apply_migration(migration, true)?;
+        applied.push(migration);
+    }
+
+    Ok(applied)
+}
+
+/// Reverts applied migrations until the schema is at version `target`, or
+/// only the latest one.
+pub fn migrate_down<S: SalesStore + ?Sized>(
+    store: &mut S,
+    target: Option<u32>,
+) -> Result<Vec<&'static Migration>, String> {
+    let current = store.schema_version()?;
+    let target = target.unwrap_or(current.saturating_sub(1));
+    let mut reverted = vec![];
+
+    for migration in store.migrations().iter().rev() {
+        if migration.version > current || migration.version <= target {
+            continue;
+        }
+        store.apply_migration(migration, false)?;
+        reverted.push(migration);
+    }
+
+    Ok(reverted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load::TableStats;
+    use crate::{Decimal, Timestamp, Unit};
+
+    fn product(id: i32, name: &str) -> Product {
+        Product::new(id, "fruit", name)
+    }
+
+    fn sale(id: &str, product_id: i32, quantity: Decimal) -> Sale {
+        Sale::new(id, product_id, Timestamp::from_epoch(1_234_567_890).unwrap(), quantity, Unit::Kilogram)
+    }
+
+    fn data() -> SalesAndProducts {
+        SalesAndProducts::new(
+            vec![product(1, "apple"), product(2, "pear")],
+            vec![sale("a", 1, Decimal::new(15, 1)), sale("b", 2, Decimal::ONE)],
+        )
+    }
+
+    fn records(data: &SalesAndProducts) -> Vec<Result<Record, String>> {
+        let products = data.products.iter().cloned().map(Record::Product);
+        let sales = data.sales.iter().cloned().map(Record::Sale);
+        products.chain(sales).map(Ok).collect()
+    }
+
+    #[test]
+    fn load_inserts_every_row() {
+        let mut store = MemoryStore::new();
+        let report = load(&mut store, &data(), LoadMode::Insert).unwrap();
+        assert_eq!(report.products.inserted, 2);
+        assert_eq!(report.sales.inserted, 2);
+        assert_eq!(query(&mut store, &DateRange::default()).unwrap(), data());
+    }
+
+    #[test]
+    fn insert_rejects_existing_rows_and_rolls_back() {
+        let mut store = MemoryStore::new();
+        load(&mut store, &data(), LoadMode::Insert).unwrap();
+
+        let mut more = data();
+        more.products = vec![product(3, "plum"), product(1, "apple")];
+        let error = load(&mut store, &more, LoadMode::Insert).unwrap_err();
+        assert_eq!(error, "duplicate product id 1");
+        assert_eq!(store.products().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn upsert_counts_changes() {
+        let mut store = MemoryStore::new();
+        load(&mut store, &data(), LoadMode::Insert).unwrap();
+
+        let mut changed = data();
+        changed.products[1].name = "quince".to_string();
+        changed.products.push(product(3, "plum"));
+        let report = load(&mut store, &changed, LoadMode::Upsert).unwrap();
+        assert_eq!(
+            report.products,
+            TableStats {
+                inserted: 1,
+                updated: 1,
+                unchanged: 1,
+                deleted: 0
+            }
+        );
+        assert_eq!(report.sales.unchanged, 2);
+        assert_eq!(store.products().unwrap()[1].name, "quince");
+    }
+
+    #[test]
+    fn sync_deletes_rows_missing_from_the_input() {
+        let mut store = MemoryStore::new();
+        load(&mut store, &data(), LoadMode::Insert).unwrap();
+
+        let kept = SalesAndProducts::new(vec![product(1, "apple")], vec![sale("a", 1, Decimal::new(15, 1))]);
+        let report = load(&mut store, &kept, LoadMode::Sync).unwrap();
+        assert_eq!(report.products.deleted, 1);
+        assert_eq!(report.sales.deleted, 1);
+        assert_eq!(query(&mut store, &DateRange::default()).unwrap(), kept);
+    }
+
+    #[test]
+    fn batch_writer_gives_the_same_report_for_any_batch_size() {
+        let mut reports = vec![];
+        for size in [1, 2, 3, 1000] {
+            let mut store = MemoryStore::new();
+            load(&mut store, &data(), LoadMode::Insert).unwrap();
+            let mut input = data();
+            input.products.remove(1);
+            input.sales.remove(1);
+            input.sales.push(sale("c", 1, Decimal::TWO));
+
+            let report = in_transaction(&mut store, |store| {
+                let mut writer = BatchWriter::new(store, LoadMode::Sync, size);
+                for record in records(&input) {
+                    writer.push(record?)?;
+                }
+                writer.finish()
+            })
+            .unwrap();
+            assert_eq!(query(&mut store, &DateRange::default()).unwrap(), input);
+            reports.push(report);
+        }
+        assert!(reports.windows(2).all(|pair| pair[0] == pair[1]), "{:?}", reports);
+        assert_eq!(reports[0].sales.inserted, 1);
+        assert_eq!(reports[0].sales.deleted, 1);
+        assert_eq!(reports[0].products.deleted, 1);
+    }
+
+    #[test]
+    fn batch_writer_writes_products_before_sales_of_a_batch() {
+        let mut store = MemoryStore::new();
+        let mut writer = BatchWriter::new(&mut store, LoadMode::Insert, 10);
+        writer.push_sale(sale("a", 1, Decimal::ONE)).unwrap();
+        writer.push_product(product(1, "apple")).unwrap();
+        let report = writer.finish().unwrap();
+        assert_eq!(report.sales.inserted, 1);
+    }
+
+    #[test]
+    fn load_records_rounds_quantities() {
+        let mut store = MemoryStore::new();
+        let mut input = data();
+        input.sales[0].quantity = Decimal::new(12345, 4);
+        let rules = QuantityRules::default();
+
+        let (report, rounded) = load_records(&mut store, records(&input), &rules, LoadMode::Insert, 1).unwrap();
+        assert_eq!(rounded, 1);
+        assert_eq!(report.sales.inserted, 2);
+        let sales = store.sales(&DateRange::default()).unwrap();
+        assert_eq!(sales[0].quantity.to_string(), "1.234");
+        assert_eq!(sales[1].quantity.to_string(), "1.000");
+    }
+
+    #[test]
+    fn load_records_rejects_invalid_records_and_rolls_back() {
+        let rules = QuantityRules::default();
+        let cases = [
+            (Record::Product(product(3, " ")), "product 3 has an empty name"),
+            (Record::Sale(sale("n", 1, Decimal::NEGATIVE_ONE)), "sale n: quantity -1 is negative"),
+            (Record::Sale(sale("m", 9, Decimal::ONE)), "sale m refers to missing product 9"),
+        ];
+        for (record, expected) in cases {
+            let mut store = MemoryStore::new();
+            let mut input = records(&data());
+            input.push(Ok(record));
+            let error = load_records(&mut store, input, &rules, LoadMode::Insert, 1).unwrap_err();
+            assert_eq!(error, expected);
+            assert!(store.products().unwrap().is_empty());
+        }
+
+        let mut store = MemoryStore::new();
+        let input = vec![Ok(Record::Product(product(1, "apple"))), Err("line 2: bad".to_string())];
+        assert_eq!(
+            load_records(&mut store, input, &rules, LoadMode::Insert, 1).unwrap_err(),
+            "line 2: bad"
+        );
+        assert!(store.products().unwrap().is_empty());
+    }
+
+    const MIGRATIONS: &[Migration] = &[
+        Migration {
+            version: 1,
+            name: "one",
+            up: "",
+            down: "",
+        },
+        Migration {
+            version: 2,
+            name: "two",
+            up: "",
+            down: "",
+        },
+        Migration {
+            version: 3,
+            name: "three",
+            up: "",
+            down: "",
+        },
+    ];
+
+    fn versions(migrations: Vec<&Migration>) -> Vec<u32> {
+        migrations.iter().map(|migration| migration.version).collect()
+    }
+
+    #[test]
+    fn migrate_up_applies_pending_migrations_up_to_the_target() {
+        let mut store = MemoryStore::with_migrations(MIGRATIONS);
+        assert_eq!(versions(migrate_up(&mut store, Some(2)).unwrap()), [1, 2]);
+        assert_eq!(store.schema_version().unwrap(), 2);
+        assert_eq!(versions(migrate_up(&mut store, None).unwrap()), [3]);
+        assert!(migrate_up(&mut store, None).unwrap().is_empty());
+        assert_eq!(store.schema_version().unwrap(), 3);
+    }
+
+    #[test]
+    fn migrate_down_reverts_the_latest_or_down_to_the_target() {
+        let mut store = MemoryStore::with_migrations(MIGRATIONS);
+        migrate_up(&mut store, None).unwrap();
+        assert_eq!(versions(migrate_down(&mut store, None).unwrap()), [3]);
+        assert_eq!(store.schema_version().unwrap(), 2);
+        assert_eq!(versions(migrate_down(&mut store, Some(0)).unwrap()), [2, 1]);
+        assert_eq!(store.schema_version().unwrap(), 0);
+        assert!(migrate_down(&mut store, None).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,506 @@
+use super::{BulkLoad, Migration, SalesStore};
+use crate::load::{RowChange, TableStats};
+use crate::{DateRange, LoadMode, LoadReport, Product, Sale};
+use chrono::{DateTime, Utc};
+use postgres::binary_copy::BinaryCopyInWriter;
+use postgres::error::SqlState;
+use postgres::types::{ToSql, Type};
+use postgres::{Client, NoTls, Row};
+use std::collections::HashSet;
+use std::time::Duration;
+
+// The first migrations use `IF NOT EXISTS` so databases created before
+// versioning are adopted instead of failing.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "create_products",
+        up: include_str!("../../migrations/postgres/0001_create_products.up.sql"),
+        down: include_str!("../../migrations/postgres/0001_create_products.down.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "create_sales",
+        up: include_str!("../../migrations/postgres/0002_create_sales.up.sql"),
+        down: include_str!("../../migrations/postgres/0002_create_sales.down.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "sales_date_timestamptz",
+        up: include_str!("../../migrations/postgres/0003_sales_date_timestamptz.up.sql"),
+        down: include_str!("../../migrations/postgres/0003_sales_date_timestamptz.down.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "sales_quantity_numeric",
+        up: include_str!("../../migrations/postgres/0004_sales_quantity_numeric.up.sql"),
+        down: include_str!("../../migrations/postgres/0004_sales_quantity_numeric.down.sql"),
+    },
+];
+
+const INSERT_PRODUCTS: &str = "INSERT INTO products (id, category, name) VALUES";
+const UPSERT_PRODUCTS: &str = "ON CONFLICT (id) DO UPDATE SET category = EXCLUDED.category, name = EXCLUDED.name
+    WHERE (products.category, products.name) IS DISTINCT FROM (EXCLUDED.category, EXCLUDED.name)
+    RETURNING (xmax = 0) AS inserted";
+const INSERT_SALES: &str = "INSERT INTO sales (id, product_id, date, quantity, unit) VALUES";
+const UPSERT_SALES: &str = "ON CONFLICT (id) DO UPDATE SET product_id = EXCLUDED.product_id, date = EXCLUDED.date,
+        quantity = EXCLUDED.quantity, unit = EXCLUDED.unit
+    WHERE (sales.product_id, sales.date, sales.quantity, sales.unit)
+        IS DISTINCT FROM (EXCLUDED.product_id, EXCLUDED.date, EXCLUDED.quantity, EXCLUDED.unit)
+    RETURNING (xmax = 0) AS inserted";
+
+/// Rows per multi-row INSERT, well under the limit of 65535 parameters a
+/// statement can take.
+const INSERT_ROWS: usize = 1000;
+
+pub struct PostgresStore {
+    client: Client,
+    bulk_load: BulkLoad,
+    in_transaction: bool,
+}
+
+impl PostgresStore {
+    pub fn connect(url: &str) -> Result<Self, String> {
+        let mut client = Client::connect(url, NoTls).map_err(db_error)?;
+        client
+            .execute(
+                "CREATE TABLE IF NOT EXISTS schema_version (
+                  version         INTEGER PRIMARY KEY,
+                  name            VARCHAR(50) NOT NULL,
+                  applied_at      TIMESTAMPTZ NOT NULL DEFAULT now()
+                  )",
+                &[],
+            )
+            .map_err(db_error)?;
+        Ok(PostgresStore {
+            client,
+            bulk_load: BulkLoad::default(),
+            in_transaction: false,
+        })
+    }
+
+    pub fn client(&mut self) -> &mut Client {
+        &mut self.client
+    }
+
+    pub fn set_bulk_load(&mut self, bulk_load: BulkLoad) {
+        self.bulk_load = bulk_load;
+    }
+
+    /// Copies the rows with `COPY`, or inserts them with multi-row INSERT
+    /// statements when the server refuses the copy, as some poolers and
+    /// hosted databases do. Later batches then go straight to INSERT.
+    fn copy_or_insert(&mut self, products: &[Product], sales: &[Sale]) -> Result<(), String> {
+        if matches!(self.bulk_load, BulkLoad::Binary | BulkLoad::Csv) {
+            // A failed statement aborts the whole transaction unless it ran
+            // inside a savepoint
+            if self.in_transaction {
+                self.client.batch_execute("SAVEPOINT bulk_copy").map_err(db_error)?;
+            }
+            let copied = self.copy(products, sales);
+            let mut ended = Ok(());
+            if self.in_transaction {
+                let end = if copied.is_ok() { "RELEASE" } else { "ROLLBACK TO" };
+                ended = self.client.batch_execute(&format!("{} SAVEPOINT bulk_copy", end));
+            }
+            match copied {
+                Ok(()) => return ended.map_err(db_error),
+                Err(CopyError::Refused(e)) => {
+                    // The client drops the connection when the server refuses
+                    // COPY before any row is sent, leaving nothing to INSERT on
+                    if ended.is_err() || self.client.is_valid(Duration::from_secs(5)).is_err() {
+                        return Err(format!("COPY was refused ({}), rerun with --bulk-load insert", e));
+                    }
+                    eprintln!("COPY was refused ({}), loading with INSERT instead", e);
+                    self.bulk_load = BulkLoad::Insert;
+                }
+                Err(CopyError::Failed(e)) => {
+                    ended.map_err(db_error)?;
+                    return Err(e);
+                }
+            }
+        }
+        self.insert_products(products, false)?;
+        self.insert_sales(sales, false)?;
+        Ok(())
+    }
+
+    fn copy(&mut self, products: &[Product], sales: &[Sale]) -> Result<(), CopyError> {
+        if !products.is_empty() {
+            let query = "COPY products (id, category, name) FROM STDIN";
+            if self.bulk_load == BulkLoad::Csv {
+                let rows = products
+                    .iter()
+                    .map(|product| [product.id.to_string(), product.category.clone(), product.name.clone()]);
+                self.copy_csv(query, rows)?;
+            } else {
+                let writer = self.client.copy_in(&format!("{} (FORMAT binary)", query))?;
+                let mut writer = BinaryCopyInWriter::new(writer, &[Type::INT4, Type::VARCHAR, Type::VARCHAR]);
+                for product in products.iter() {
+                    writer.write(&[&product.id, &product.category, &product.name])?;
+                }
+                writer.finish()?;
+            }
+        }
+
+        if !sales.is_empty() {
+            let query = "COPY sales (id, product_id, date, quantity, unit) FROM STDIN";
+            if self.bulk_load == BulkLoad::Csv {
+                let rows = sales.iter().map(|sale| {
+                    [
+                        sale.id.clone(),
+                        sale.product_id.to_string(),
+                        sale.date.to_string(),
+                        sale.quantity.to_string(),
+                        sale.unit.symbol().to_string(),
+                    ]
+                });
+                self.copy_csv(query, rows)?;
+            } else {
+                let writer = self.client.copy_in(&format!("{} (FORMAT binary)", query))?;
+                let types = [Type::VARCHAR, Type::INT4, Type::TIMESTAMPTZ, Type::NUMERIC, Type::VARCHAR];
+                let mut writer = BinaryCopyInWriter::new(writer, &types);
+                for sale in sales.iter() {
+                    writer.write(&[
+                        &sale.id,
+                        &sale.product_id,
+                        &sale.date.datetime(),
+                        &sale.quantity,
+                        &sale.unit.symbol(),
+                    ])?;
+                }
+                writer.finish()?;
+            }
+        }
+        Ok(())
+    }
+
+    // Dropping the copy writer without finishing it aborts the copy
+    fn copy_csv<I, R>(&mut self, query: &str, rows: I) -> Result<(), CopyError>
+    where
+        I: IntoIterator<Item = R>,
+        R: IntoIterator<Item = String>,
+    {
+        let mut writer = self.client.copy_in(&format!("{} (FORMAT csv)", query))?;
+        {
+            // Every field is quoted, since COPY reads an empty unquoted field
+            // as NULL
+            let mut csv = ::csv::WriterBuilder::new()
+                .has_headers(false)
+                .quote_style(::csv::QuoteStyle::Always)
+                .from_writer(&mut writer);
+            for row in rows {
+                csv.write_record(row)?;
+            }
+            csv.flush()?;
+        }
+        writer.finish()?;
+        Ok(())
+    }
+
+    /// Inserts the products with multi-row statements, or upserts them
+    /// counting the rows inserted, updated and left unchanged.
+    fn insert_products(&mut self, products: &[Product], upsert: bool) -> Result<TableStats, String> {
+        let mut stats = TableStats::default();
+        for chunk in products.chunks(INSERT_ROWS) {
+            let params: Vec<&(dyn ToSql + Sync)> = chunk
+                .iter()
+                .flat_map(|product| [&product.id as &(dyn ToSql + Sync), &product.category, &product.name])
+                .collect();
+            stats += self.insert_rows(INSERT_PRODUCTS, UPSERT_PRODUCTS, upsert, chunk.len(), &params)?;
+        }
+        Ok(stats)
+    }
+
+    fn insert_sales(&mut self, sales: &[Sale], upsert: bool) -> Result<TableStats, String> {
+        let mut stats = TableStats::default();
+        for chunk in sales.chunks(INSERT_ROWS) {
+            let dates: Vec<DateTime<Utc>> = chunk.iter().map(|sale| sale.date.datetime()).collect();
+            let units: Vec<&str> = chunk.iter().map(|sale| sale.unit.symbol()).collect();
+            let params: Vec<&(dyn ToSql + Sync)> = chunk
+                .iter()
+                .zip(dates.iter().zip(units.iter()))
+                .flat_map(|(sale, (date, unit))| {
+                    [&sale.id as &(dyn ToSql + Sync), &sale.product_id, date, &sale.quantity, unit]
+                })
+                .collect();
+            stats += self.insert_rows(INSERT_SALES, UPSERT_SALES, upsert, chunk.len(), &params)?;
+        }
+        Ok(stats)
+    }
+
+    fn insert_rows(
+        &mut self,
+        insert: &str,
+        on_conflict: &str,
+        upsert: bool,
+        rows: usize,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> Result<TableStats, String> {
+        let columns = params.len() / rows;
+        let values: Vec<String> = (0..rows)
+            .map(|row| {
+                let placeholders: Vec<String> = (1..=columns).map(|column| format!("${}", row * columns + column)).collect();
+                format!("({})", placeholders.join(", "))
+            })
+            .collect();
+        let mut stats = TableStats::default();
+
+        if !upsert {
+            self.client
+                .execute(&format!("{} {}", insert, values.join(", ")), params)
+                .map_err(db_error)?;
+            stats.inserted = rows;
+            return Ok(stats);
+        }
+
+        let changed = self
+            .client
+            .query(&format!("{} {} {}", insert, values.join(", "), on_conflict), params)
+            .map_err(db_error)?;
+        for row in changed {
+            stats.record(row_change(Some(row)));
+        }
+        stats.unchanged = rows - stats.inserted - stats.updated;
+        Ok(stats)
+    }
+}
+
+enum CopyError {
+    /// The server does not allow `COPY` here, so INSERT may still work
+    Refused(String),
+    Failed(String),
+}
+
+impl From<postgres::Error> for CopyError {
+    fn from(e: postgres::Error) -> Self {
+        match e.code() {
+            Some(&SqlState::INSUFFICIENT_PRIVILEGE | &SqlState::FEATURE_NOT_SUPPORTED) => CopyError::Refused(db_error(e)),
+            _ => CopyError::Failed(db_error(e)),
+        }
+    }
+}
+
+impl From<::csv::Error> for CopyError {
+    fn from(e: ::csv::Error) -> Self {
+        CopyError::Failed(e.to_string())
+    }
+}
+
+impl From<std::io::Error> for CopyError {
+    fn from(e: std::io::Error) -> Self {
+        CopyError::Failed(e.to_string())
+    }
+}
+
+/// The message of a server error, with its detail and constraint, since the
+/// `Display` of `postgres::Error` only says "db error".
+fn db_error(e: postgres::Error) -> String {
+    let Some(db) = e.as_db_error() else {
+        return e.to_string();
+    };
+    let mut message = db.message().to_string();
+    if let Some(detail) = db.detail() {
+        message.push_str(&format!(": {}", detail));
+    }
+    if let Some(constraint) = db.constraint().filter(|constraint| !message.contains(constraint)) {
+        message.push_str(&format!(" (constraint {})", constraint));
+    }
+    message
+}
+
+// The upsert returns no row when the WHERE clause skipped the update, and
+// `xmax = 0` only for freshly inserted rows.
+fn row_change(row: Option<Row>) -> RowChange {
+    match row {
+        None => RowChange::Unchanged,
+        Some(row) if row.get::<_, bool>(0) => RowChange::Inserted,
+        Some(_) => RowChange::Updated,
+    }
+}
+
+impl SalesStore for PostgresStore {
+    fn migrations(&self) -> &'static [Migration] {
+        MIGRATIONS
+    }
+
+    fn schema_version(&mut self) -> Result<u32, String> {
+        let row = self
+            .client
+            .query_one("SELECT COALESCE(MAX(version), 0) FROM schema_version", &[])
+            .map_err(db_error)?;
+        let version: i32 = row.get(0);
+        Ok(version as u32)
+    }
+
+    fn apply_migration(&mut self, migration: &Migration, up: bool) -> Result<(), String> {
+        let mut tx = self.client.transaction().map_err(db_error)?;
+        if up {
+            tx.batch_execute(migration.up).map_err(db_error)?;
+            tx.execute(
+                "INSERT INTO schema_version (version, name) VALUES ($1, $2)",
+                &[&(migration.version as i32), &migration.name],
+            )
+            .map_err(db_error)?;
+        } else {
+            tx.batch_execute(migration.down).map_err(db_error)?;
+            tx.execute(
+                "DELETE FROM schema_version WHERE version = $1",
+                &[&(migration.version as i32)],
+            )
+            .map_err(db_error)?;
+        }
+        tx.commit().map_err(db_error)
+    }
+
+    fn begin(&mut self) -> Result<(), String> {
+        self.client.batch_execute("BEGIN").map_err(db_error)?;
+        self.in_transaction = true;
+        Ok(())
+    }
+
+    fn commit(&mut self) -> Result<(), String> {
+        self.in_transaction = false;
+        self.client.batch_execute("COMMIT").map_err(db_error)
+    }
+
+    fn rollback(&mut self) -> Result<(), String> {
+        self.in_transaction = false;
+        self.client.batch_execute("ROLLBACK").map_err(db_error)
+    }
+
+    fn insert_product(&mut self, product: &Product) -> Result<(), String> {
+        self.client
+            .execute(
+                "INSERT INTO products (id, category, name) VALUES ($1, $2, $3)",
+                &[&product.id, &product.category, &product.name],
+            )
+            .map(|_| ())
+            .map_err(db_error)
+    }
+
+    fn insert_sale(&mut self, sale: &Sale) -> Result<(), String> {
+        self.client
+            .execute(
+                "INSERT INTO sales (id, product_id, date, quantity, unit) VALUES ($1, $2, $3, $4, $5)",
+                &[
+                    &sale.id,
+                    &sale.product_id,
+                    &sale.date.datetime(),
+                    &sale.quantity,
+                    &sale.unit.symbol()
+                ],
+            )
+            .map(|_| ())
+            .map_err(db_error)
+    }
+
+    fn upsert_product(&mut self, product: &Product) -> Result<RowChange, String> {
+        let row = self
+            .client
+            .query_opt(
+                &format!("{} ($1, $2, $3) {}", INSERT_PRODUCTS, UPSERT_PRODUCTS),
+                &[&product.id, &product.category, &product.name],
+            )
+            .map_err(db_error)?;
+        Ok(row_change(row))
+    }
+
+    fn upsert_sale(&mut self, sale: &Sale) -> Result<RowChange, String> {
+        let row = self
+            .client
+            .query_opt(
+                &format!("{} ($1, $2, $3, $4, $5) {}", INSERT_SALES, UPSERT_SALES),
+                &[
+                    &sale.id,
+                    &sale.product_id,
+                    &sale.date.datetime(),
+                    &sale.quantity,
+                    &sale.unit.symbol()
+                ],
+            )
+            .map_err(db_error)?;
+        Ok(row_change(row))
+    }
+
+    fn write_batch(&mut self, products: &[Product], sales: &[Sale], mode: LoadMode) -> Result<LoadReport, String> {
+        if self.bulk_load == BulkLoad::Row {
+            return super::write_rows(self, products, sales, mode);
+        }
+        let mut report = LoadReport::default();
+        match mode {
+            LoadMode::Insert => {
+                self.copy_or_insert(products, sales)?;
+                report.products.inserted = products.len();
+                report.sales.inserted = sales.len();
+            }
+            LoadMode::Upsert | LoadMode::Sync => {
+                // One statement cannot update the same row twice
+                let product_ids: HashSet<i32> = products.iter().map(|product| product.id).collect();
+                let sale_ids: HashSet<&str> = sales.iter().map(|sale| sale.id.as_str()).collect();
+                if product_ids.len() < products.len() || sale_ids.len() < sales.len() {
+                    return super::write_rows(self, products, sales, mode);
+                }
+                report.products = self.insert_products(products, true)?;
+                report.sales = self.insert_sales(sales, true)?;
+            }
+        }
+        Ok(report)
+    }
+
+    fn delete_products_except(&mut self, keep: &HashSet<i32>) -> Result<usize, String> {
+        let keep: Vec<i32> = keep.iter().copied().collect();
+        self.client
+            .execute("DELETE FROM products WHERE id <> ALL($1)", &[&keep])
+            .map(|deleted| deleted as usize)
+            .map_err(db_error)
+    }
+
+    fn delete_sales_except(&mut self, keep: &HashSet<String>) -> Result<usize, String> {
+        let keep: Vec<&str> = keep.iter().map(|id| id.as_str()).collect();
+        self.client
+            .execute("DELETE FROM sales WHERE id <> ALL($1)", &[&keep])
+            .map(|deleted| deleted as usize)
+            .map_err(db_error)
+    }
+
+    fn products(&mut self) -> Result<Vec<Product>, String> {
+        let rows = self
+            .client
+            .query("SELECT id, category, name FROM products ORDER BY id", &[])
+            .map_err(db_error)?;
+        Ok(rows
+            .iter()
+            .map(|row| Product {
+                id: row.get(0),
+                category: row.get(1),
+                name: row.get(2),
+            })
+            .collect())
+    }
+
+    fn sales(&mut self, range: &DateRange) -> Result<Vec<Sale>, String> {
+        let from = range.from.map(|from| from.datetime());
+        let to = range.to.map(|to| to.datetime());
+        let rows = self
+            .client
+            .query(
+                "SELECT id, product_id, date, quantity, unit FROM sales
+                    WHERE ($1::TIMESTAMPTZ IS NULL OR date >= $1) AND ($2::TIMESTAMPTZ IS NULL OR date < $2)
+                    ORDER BY id",
+                &[&from, &to],
+            )
+            .map_err(db_error)?;
+        rows.iter()
+            .map(|row| {
+                Ok(Sale {
+                    id: row.get(0),
+                    product_id: row.get(1),
+                    date: row.get::<_, DateTime<Utc>>(2).into(),
+                    quantity: row.get(3),
+                    unit: row.get::<_, &str>(4).parse()?,
+                })
+            })
+            .collect()
+    }
+}
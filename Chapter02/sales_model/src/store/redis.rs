@@ -0,0 +1,672 @@
+use super::{Migration, SalesStore};
+use crate::load::RowChange;
+use crate::{quantity, DateRange, Product, Sale, Timestamp};
+use redis::{Commands, Connection, ConnectionLike, Pipeline};
+use std::collections::{HashMap, HashSet};
+
+// Products are hashes at `product:<id>` and sales hashes at `sale:<id>`,
+// with the ids of each kind in the `products` and `sales` sets. The sales of
+// a product are also in the sorted set `product:<id>:sales`, scored by date,
+// which is how date ranges are read.
+const PRODUCTS: &str = "products";
+const SALES: &str = "sales";
+
+fn product_key(id: i32) -> String {
+    format!("product:{}", id)
+}
+
+fn product_sales_key(id: i32) -> String {
+    format!("product:{}:sales", id)
+}
+
+fn sale_key(id: &str) -> String {
+    format!("sale:{}", id)
+}
+
+/// A store in Redis. Writes made inside a transaction are queued and sent
+/// together in a `MULTI`/`EXEC` block on commit, so reads made inside it do
+/// not see them. Any [`ConnectionLike`] can back the store, such as a mock
+/// connection in tests.
+pub struct RedisStore<C = Connection> {
+    conn: C,
+    pending: Option<Pipeline>,
+    /// Keys created by the queued writes, to enforce ids and product
+    /// references before they reach Redis
+    pending_keys: HashSet<String>,
+    /// The product of each sale written by the queued writes, and the sales
+    /// they delete, so that products are not deleted from under their sales
+    pending_sales: HashMap<String, i32>,
+    deleted_sales: HashSet<String>,
+}
+
+impl RedisStore<Connection> {
+    /// Connects to a `redis://host[:port][/db]` URL.
+    pub fn connect(url: &str) -> Result<Self, String> {
+        let client = redis::Client::open(url).map_err(|e| e.to_string())?;
+        let conn = client.get_connection().map_err(|e| e.to_string())?;
+        Ok(RedisStore::new(conn))
+    }
+}
+
+impl<C: ConnectionLike> RedisStore<C> {
+    pub fn new(conn: C) -> Self {
+        RedisStore {
+            conn,
+            pending: None,
+            pending_keys: HashSet::new(),
+            pending_sales: HashMap::new(),
+            deleted_sales: HashSet::new(),
+        }
+    }
+
+    pub fn connection(&mut self) -> &mut C {
+        &mut self.conn
+    }
+
+    fn exists(&mut self, key: &str) -> Result<bool, String> {
+        if self.pending_keys.contains(key) {
+            return Ok(true);
+        }
+        self.conn.exists(key).map_err(|e| e.to_string())
+    }
+
+    /// Queues the commands added by `build` when a transaction is open, or
+    /// runs them atomically right away.
+    fn write<F: FnOnce(&mut Pipeline)>(&mut self, build: F) -> Result<(), String> {
+        if let Some(pipe) = self.pending.as_mut() {
+            build(pipe);
+            return Ok(());
+        }
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        build(&mut pipe);
+        pipe.query::<()>(&mut self.conn).map_err(|e| e.to_string())
+    }
+
+    fn write_product(&mut self, product: &Product) -> Result<(), String> {
+        let key = product_key(product.id);
+        self.write(|pipe| {
+            pipe.hset_multiple(
+                &key,
+                &[
+                    ("id", product.id.to_string()),
+                    ("category", product.category.clone()),
+                    ("name", product.name.clone()),
+                ],
+            )
+            .ignore()
+            .sadd(PRODUCTS, product.id)
+            .ignore();
+        })?;
+        self.pending_keys.insert(key);
+        Ok(())
+    }
+
+    /// Writes `sale`, moving it out of the sorted set of `previous` when the
+    /// sale used to belong to another product.
+    fn write_sale(&mut self, sale: &Sale, previous: Option<&Sale>) -> Result<(), String> {
+        if !self.exists(&product_key(sale.product_id))? {
+            return Err(format!(
+                "sale {} refers to missing product {}",
+                sale.id, sale.product_id
+            ));
+        }
+        let key = sale_key(&sale.id);
+        self.write(|pipe| {
+            if let Some(previous) = previous.filter(|previous| previous.product_id != sale.product_id) {
+                pipe.zrem(product_sales_key(previous.product_id), &sale.id).ignore();
+            }
+            pipe.hset_multiple(
+                &key,
+                &[
+                    ("id", sale.id.clone()),
+                    ("product_id", sale.product_id.to_string()),
+                    ("date", sale.date.epoch().to_string()),
+                    ("quantity", sale.quantity.to_string()),
+                    ("unit", sale.unit.symbol().to_string()),
+                ],
+            )
+            .ignore()
+            .sadd(SALES, &sale.id)
+            .ignore()
+            .zadd(product_sales_key(sale.product_id), &sale.id, sale.date.epoch())
+            .ignore();
+        })?;
+        self.pending_keys.insert(key);
+        if self.pending.is_some() {
+            self.pending_sales.insert(sale.id.clone(), sale.product_id);
+            self.deleted_sales.remove(&sale.id);
+        }
+        Ok(())
+    }
+
+    /// The ids of the sales of a product, as they will be once the queued
+    /// writes are sent.
+    fn product_sale_ids(&mut self, product_id: i32) -> Result<Vec<String>, String> {
+        let stored: Vec<String> = self
+            .conn
+            .zrange(product_sales_key(product_id), 0, -1)
+            .map_err(|e| e.to_string())?;
+        let queued = self.pending_sales.iter().filter(|(_, id)| **id == product_id).map(|(sale, _)| sale);
+        let mut ids: Vec<String> = stored
+            .into_iter()
+            .filter(|sale| !self.pending_sales.contains_key(sale))
+            .chain(queued.cloned())
+            .filter(|sale| !self.deleted_sales.contains(sale))
+            .collect();
+        ids.sort();
+        Ok(ids)
+    }
+
+    fn clear_pending(&mut self) {
+        self.pending_keys.clear();
+        self.pending_sales.clear();
+        self.deleted_sales.clear();
+    }
+
+    fn read_product(&mut self, id: i32) -> Result<Option<Product>, String> {
+        let fields: HashMap<String, String> = self.conn.hgetall(product_key(id)).map_err(|e| e.to_string())?;
+        if fields.is_empty() {
+            return Ok(None);
+        }
+        let field = |name: &str| field(&fields, &product_key(id), name);
+        Ok(Some(Product {
+            id,
+            category: field("category")?.to_string(),
+            name: field("name")?.to_string(),
+        }))
+    }
+
+    fn read_sale(&mut self, id: &str) -> Result<Option<Sale>, String> {
+        let key = sale_key(id);
+        let fields: HashMap<String, String> = self.conn.hgetall(&key).map_err(|e| e.to_string())?;
+        if fields.is_empty() {
+            return Ok(None);
+        }
+        let field = |name: &str| field(&fields, &key, name);
+        let invalid = |name: &str, e: String| format!("{} {}: {}", key, name, e);
+        Ok(Some(Sale {
+            id: id.to_string(),
+            product_id: field("product_id")?
+                .parse()
+                .map_err(|e: std::num::ParseIntError| invalid("product_id", e.to_string()))?,
+            date: field("date")?
+                .parse()
+                .map_err(|e: std::num::ParseIntError| invalid("date", e.to_string()))
+                .and_then(|seconds| Timestamp::from_epoch(seconds).map_err(|e| invalid("date", e)))?,
+            quantity: quantity::parse(field("quantity")?).map_err(|e| invalid("quantity", e))?,
+            unit: field("unit")?.parse().map_err(|e| invalid("unit", e))?,
+        }))
+    }
+
+    fn product_ids(&mut self) -> Result<Vec<i32>, String> {
+        let ids: Vec<String> = self.conn.smembers(PRODUCTS).map_err(|e| e.to_string())?;
+        let mut ids = ids
+            .iter()
+            .map(|id| id.parse().map_err(|_| format!("invalid product id `{}` in {}", id, PRODUCTS)))
+            .collect::<Result<Vec<i32>, String>>()?;
+        ids.sort();
+        Ok(ids)
+    }
+}
+
+fn field<'a>(fields: &'a HashMap<String, String>, key: &str, name: &str) -> Result<&'a str, String> {
+    fields
+        .get(name)
+        .map(|value| value.as_str())
+        .ok_or_else(|| format!("{} has no `{}` field", key, name))
+}
+
+impl<C: ConnectionLike> SalesStore for RedisStore<C> {
+    fn migrations(&self) -> &'static [Migration] {
+        &[]
+    }
+
+    fn schema_version(&mut self) -> Result<u32, String> {
+        Ok(0)
+    }
+
+    fn apply_migration(&mut self, migration: &Migration, _up: bool) -> Result<(), String> {
+        Err(format!("redis has no schema to apply {} to", migration.name))
+    }
+
+    fn begin(&mut self) -> Result<(), String> {
+        if self.pending.is_some() {
+            return Err("a transaction is already open".to_string());
+        }
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        self.pending = Some(pipe);
+        Ok(())
+    }
+
+    fn commit(&mut self) -> Result<(), String> {
+        let pipe = self.pending.take().ok_or_else(|| "no open transaction".to_string())?;
+        self.clear_pending();
+        pipe.query::<()>(&mut self.conn).map_err(|e| e.to_string())
+    }
+
+    fn rollback(&mut self) -> Result<(), String> {
+        self.pending.take().ok_or_else(|| "no open transaction".to_string())?;
+        self.clear_pending();
+        Ok(())
+    }
+
+    fn insert_product(&mut self, product: &Product) -> Result<(), String> {
+        if self.exists(&product_key(product.id))? {
+            return Err(format!("duplicate product id {}", product.id));
+        }
+        self.write_product(product)
+    }
+
+    fn insert_sale(&mut self, sale: &Sale) -> Result<(), String> {
+        if self.exists(&sale_key(&sale.id))? {
+            return Err(format!("duplicate sale id {}", sale.id));
+        }
+        self.write_sale(sale, None)
+    }
+
+    fn upsert_product(&mut self, product: &Product) -> Result<RowChange, String> {
+        let change = match self.read_product(product.id)? {
+            None => RowChange::Inserted,
+            Some(existing) if existing == *product => return Ok(RowChange::Unchanged),
+            Some(_) => RowChange::Updated,
+        };
+        self.write_product(product)?;
+        Ok(change)
+    }
+
+    fn upsert_sale(&mut self, sale: &Sale) -> Result<RowChange, String> {
+        let existing = self.read_sale(&sale.id)?;
+        let change = match &existing {
+            None => RowChange::Inserted,
+            Some(existing) if existing == sale => return Ok(RowChange::Unchanged),
+            Some(_) => RowChange::Updated,
+        };
+        self.write_sale(sale, existing.as_ref())?;
+        Ok(change)
+    }
+
+    fn delete_products_except(&mut self, keep: &HashSet<i32>) -> Result<usize, String> {
+        let ids: Vec<i32> = self.product_ids()?.into_iter().filter(|id| !keep.contains(id)).collect();
+        // Redis has no foreign keys, so check that no remaining sale refers
+        // to a deleted product, as the other stores do
+        for id in ids.iter() {
+            if let Some(sale) = self.product_sale_ids(*id)?.first() {
+                return Err(format!("cannot delete product {}, sale {} refers to it", id, sale));
+            }
+        }
+        self.write(|pipe| {
+            for id in ids.iter() {
+                pipe.del(product_key(*id))
+                    .ignore()
+                    .del(product_sales_key(*id))
+                    .ignore()
+                    .srem(PRODUCTS, *id)
+                    .ignore();
+            }
+        })?;
+        Ok(ids.len())
+    }
+
+    fn delete_sales_except(&mut self, keep: &HashSet<String>) -> Result<usize, String> {
+        let ids: Vec<String> = self.conn.smembers(SALES).map_err(|e| e.to_string())?;
+        let mut deleted = vec![];
+        for id in ids.into_iter().filter(|id| !keep.contains(id)) {
+            let product_id: Option<i32> = self.conn.hget(sale_key(&id), "product_id").map_err(|e| e.to_string())?;
+            deleted.push((id, product_id));
+        }
+        self.write(|pipe| {
+            for (id, product_id) in deleted.iter() {
+                if let Some(product_id) = product_id {
+                    pipe.zrem(product_sales_key(*product_id), id).ignore();
+                }
+                pipe.del(sale_key(id)).ignore().srem(SALES, id).ignore();
+            }
+        })?;
+        if self.pending.is_some() {
+            for (id, _) in deleted.iter() {
+                self.pending_sales.remove(id);
+                self.deleted_sales.insert(id.clone());
+            }
+        }
+        Ok(deleted.len())
+    }
+
+    fn products(&mut self) -> Result<Vec<Product>, String> {
+        let mut products = vec![];
+        for id in self.product_ids()? {
+            if let Some(product) = self.read_product(id)? {
+                products.push(product);
+            }
+        }
+        Ok(products)
+    }
+
+    fn sales(&mut self, range: &DateRange) -> Result<Vec<Sale>, String> {
+        let min = range.from.map_or("-inf".to_string(), |from| from.epoch().to_string());
+        // `(` makes the bound exclusive
+        let max = range.to.map_or("+inf".to_string(), |to| format!("({}", to.epoch()));
+
+        let mut sales = vec![];
+        for product_id in self.product_ids()? {
+            let ids: Vec<String> = self
+                .conn
+                .zrangebyscore(product_sales_key(product_id), &min, &max)
+                .map_err(|e| e.to_string())?;
+            for id in ids {
+                if let Some(sale) = self.read_sale(&id)? {
+                    sales.push(sale);
+                }
+            }
+        }
+        sales.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(sales)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{self, LoadMode};
+    use crate::{Decimal, SalesAndProducts, Unit};
+    use redis::{RedisResult, Value};
+    use std::collections::BTreeSet;
+
+    /// An in-process stand-in for a Redis server, answering the commands the
+    /// store sends. `MULTI` queues commands until `EXEC`, as Redis does.
+    #[derive(Default)]
+    struct FakeRedis {
+        hashes: HashMap<String, HashMap<String, String>>,
+        sets: HashMap<String, BTreeSet<String>>,
+        sorted_sets: HashMap<String, HashMap<String, i64>>,
+        queued: Option<Vec<Vec<String>>>,
+    }
+
+    fn parse_commands(packed: &[u8]) -> Vec<Vec<String>> {
+        let text = std::str::from_utf8(packed).unwrap();
+        let mut lines = text.split("\r\n");
+        let mut commands = vec![];
+        while let Some(line) = lines.next().filter(|line| !line.is_empty()) {
+            let count: usize = line.strip_prefix('*').unwrap().parse().unwrap();
+            let command = (0..count)
+                .map(|_| {
+                    lines.next().unwrap();
+                    lines.next().unwrap().to_string()
+                })
+                .collect();
+            commands.push(command);
+        }
+        commands
+    }
+
+    fn data(value: &str) -> Value {
+        Value::Data(value.as_bytes().to_vec())
+    }
+
+    fn in_range(score: i64, min: &str, max: &str) -> bool {
+        let above = match min {
+            "-inf" => true,
+            min => min.strip_prefix('(').map_or_else(
+                || score >= min.parse().unwrap(),
+                |min| score > min.parse().unwrap(),
+            ),
+        };
+        let below = match max {
+            "+inf" => true,
+            max => max.strip_prefix('(').map_or_else(
+                || score <= max.parse().unwrap(),
+                |max| score < max.parse().unwrap(),
+            ),
+        };
+        above && below
+    }
+
+    impl FakeRedis {
+        fn sorted(&self, key: &str, min: &str, max: &str) -> Value {
+            let mut members: Vec<(&i64, &String)> = self
+                .sorted_sets
+                .get(key)
+                .into_iter()
+                .flatten()
+                .map(|(member, score)| (score, member))
+                .filter(|(score, _)| in_range(**score, min, max))
+                .collect();
+            members.sort();
+            Value::Bulk(members.into_iter().map(|(_, member)| data(member)).collect())
+        }
+
+        fn run(&mut self, command: Vec<String>) -> Value {
+            let name = command[0].to_uppercase();
+            if let Some(queued) = self.queued.as_mut() {
+                if name != "EXEC" {
+                    queued.push(command);
+                    return Value::Status("QUEUED".to_string());
+                }
+            }
+            let args = &command[1..];
+            let key = args.first().cloned().unwrap_or_default();
+            match name.as_str() {
+                "MULTI" => {
+                    self.queued = Some(vec![]);
+                    Value::Okay
+                }
+                "EXEC" => {
+                    let queued = self.queued.take().unwrap();
+                    Value::Bulk(queued.into_iter().map(|command| self.run(command)).collect())
+                }
+                "EXISTS" => Value::Int(
+                    (self.hashes.contains_key(&key) || self.sets.contains_key(&key) || self.sorted_sets.contains_key(&key))
+                        as i64,
+                ),
+                "DEL" => {
+                    let removed = self.hashes.remove(&key).is_some()
+                        | self.sets.remove(&key).is_some()
+                        | self.sorted_sets.remove(&key).is_some();
+                    Value::Int(removed as i64)
+                }
+                "HMSET" => {
+                    let hash = self.hashes.entry(key).or_default();
+                    for pair in args[1..].chunks(2) {
+                        hash.insert(pair[0].clone(), pair[1].clone());
+                    }
+                    Value::Okay
+                }
+                "HGET" => self
+                    .hashes
+                    .get(&key)
+                    .and_then(|hash| hash.get(&args[1]))
+                    .map_or(Value::Nil, |value| data(value)),
+                "HGETALL" => Value::Bulk(
+                    self.hashes
+                        .get(&key)
+                        .into_iter()
+                        .flatten()
+                        .flat_map(|(field, value)| [data(field), data(value)])
+                        .collect(),
+                ),
+                "SADD" => Value::Int(self.sets.entry(key).or_default().insert(args[1].clone()) as i64),
+                "SREM" => {
+                    let set = self.sets.entry(key.clone()).or_default();
+                    let removed = set.remove(&args[1]);
+                    if set.is_empty() {
+                        self.sets.remove(&key);
+                    }
+                    Value::Int(removed as i64)
+                }
+                "SMEMBERS" => Value::Bulk(self.sets.get(&key).into_iter().flatten().map(|member| data(member)).collect()),
+                "ZADD" => {
+                    let score = args[1].parse().unwrap();
+                    let added = self.sorted_sets.entry(key).or_default().insert(args[2].clone(), score);
+                    Value::Int(added.is_none() as i64)
+                }
+                "ZREM" => {
+                    let set = self.sorted_sets.entry(key.clone()).or_default();
+                    let removed = set.remove(&args[1]);
+                    if set.is_empty() {
+                        self.sorted_sets.remove(&key);
+                    }
+                    Value::Int(removed.is_some() as i64)
+                }
+                "ZRANGE" => self.sorted(&key, "-inf", "+inf"),
+                "ZRANGEBYSCORE" => self.sorted(&key, &args[1], &args[2]),
+                _ => panic!("unexpected command {:?}", command),
+            }
+        }
+    }
+
+    impl ConnectionLike for FakeRedis {
+        fn req_packed_command(&mut self, cmd: &[u8]) -> RedisResult<Value> {
+            Ok(self.run(parse_commands(cmd).remove(0)))
+        }
+
+        fn req_packed_commands(&mut self, cmd: &[u8], offset: usize, count: usize) -> RedisResult<Vec<Value>> {
+            let replies: Vec<Value> = parse_commands(cmd).into_iter().map(|command| self.run(command)).collect();
+            Ok(replies.into_iter().skip(offset).take(count).collect())
+        }
+
+        fn get_db(&self) -> i64 {
+            0
+        }
+
+        fn check_connection(&mut self) -> bool {
+            true
+        }
+
+        fn is_open(&self) -> bool {
+            true
+        }
+    }
+
+    fn product(id: i32, name: &str) -> Product {
+        Product::new(id, "fruit", name)
+    }
+
+    fn sale(id: &str, product_id: i32, epoch: i64) -> Sale {
+        Sale::new(id, product_id, Timestamp::from_epoch(epoch).unwrap(), Decimal::new(1500, 3), Unit::Kilogram)
+    }
+
+    fn sample() -> SalesAndProducts {
+        SalesAndProducts::new(
+            vec![product(1, "apple"), product(2, "pear")],
+            vec![sale("a", 1, 1_000), sale("b", 2, 2_000), sale("c", 1, 3_000)],
+        )
+    }
+
+    fn loaded() -> RedisStore<FakeRedis> {
+        let mut store = RedisStore::new(FakeRedis::default());
+        store::load(&mut store, &sample(), LoadMode::Insert).unwrap();
+        store
+    }
+
+    #[test]
+    fn load_writes_hashes_sets_and_sorted_sets() {
+        let mut store = loaded();
+        assert_eq!(store::query(&mut store, &DateRange::default()).unwrap(), sample());
+
+        let redis = store.connection();
+        assert_eq!(redis.hashes["product:2"]["name"], "pear");
+        assert_eq!(redis.hashes["sale:b"]["quantity"], "1.500");
+        assert_eq!(redis.sets[SALES].len(), 3);
+        assert_eq!(redis.sorted_sets["product:1:sales"]["c"], 3_000);
+    }
+
+    #[test]
+    fn sales_are_read_by_date_range() {
+        let mut store = loaded();
+        let range = DateRange {
+            from: Some(Timestamp::from_epoch(1_000).unwrap()),
+            to: Some(Timestamp::from_epoch(3_000).unwrap()),
+        };
+        let ids: Vec<String> = store.sales(&range).unwrap().into_iter().map(|sale| sale.id).collect();
+        assert_eq!(ids, ["a", "b"]);
+    }
+
+    #[test]
+    fn insert_rejects_duplicates_and_missing_products() {
+        let mut store = loaded();
+        assert_eq!(store.insert_product(&product(1, "apple")).unwrap_err(), "duplicate product id 1");
+        assert_eq!(store.insert_sale(&sale("z", 9, 0)).unwrap_err(), "sale z refers to missing product 9");
+    }
+
+    #[test]
+    fn rollback_discards_queued_writes() {
+        let mut store = loaded();
+        let mut more = sample();
+        more.products.push(product(3, "plum"));
+        more.sales.push(sale("d", 3, 4_000));
+        more.sales.push(sale("a", 1, 1_000));
+        let error = store::load(&mut store, &more, LoadMode::Insert).unwrap_err();
+        assert_eq!(error, "duplicate product id 1");
+        assert!(!store.connection().hashes.contains_key("product:3"));
+    }
+
+    #[test]
+    fn upsert_counts_changes_and_moves_sales_between_products() {
+        let mut store = loaded();
+        let mut changed = sample();
+        changed.products[0].name = "quince".to_string();
+        changed.sales[0].product_id = 2;
+        changed.sales.push(sale("d", 2, 4_000));
+        let report = store::load(&mut store, &changed, LoadMode::Upsert).unwrap();
+
+        assert_eq!((report.products.updated, report.products.unchanged), (1, 1));
+        assert_eq!((report.sales.inserted, report.sales.updated, report.sales.unchanged), (1, 1, 2));
+        assert_eq!(store::query(&mut store, &DateRange::default()).unwrap(), changed);
+        let redis = store.connection();
+        assert!(!redis.sorted_sets["product:1:sales"].contains_key("a"));
+        assert!(redis.sorted_sets["product:2:sales"].contains_key("a"));
+    }
+
+    #[test]
+    fn upsert_reads_inside_a_transaction_do_not_see_queued_writes() {
+        let mut store = loaded();
+        store.begin().unwrap();
+        assert_eq!(store.upsert_product(&product(3, "plum")).unwrap(), RowChange::Inserted);
+        // The sale may refer to the product queued above, but reads still go
+        // to Redis, so upserting the product again counts it as inserted
+        assert_eq!(store.upsert_sale(&sale("d", 3, 4_000)).unwrap(), RowChange::Inserted);
+        assert_eq!(store.upsert_product(&product(3, "damson")).unwrap(), RowChange::Inserted);
+        assert!(store.read_product(3).unwrap().is_none());
+        assert_eq!(store.products().unwrap().len(), 2);
+
+        store.commit().unwrap();
+        assert_eq!(store.read_product(3).unwrap(), Some(product(3, "damson")));
+        assert_eq!(store.read_sale("d").unwrap(), Some(sale("d", 3, 4_000)));
+    }
+
+    #[test]
+    fn sync_deletes_rows_missing_from_the_input() {
+        let mut store = loaded();
+        let kept = SalesAndProducts::new(vec![product(1, "apple")], vec![sale("a", 1, 1_000)]);
+        let report = store::load(&mut store, &kept, LoadMode::Sync).unwrap();
+
+        assert_eq!((report.products.deleted, report.sales.deleted), (1, 2));
+        assert_eq!(store::query(&mut store, &DateRange::default()).unwrap(), kept);
+        let redis = store.connection();
+        assert!(!redis.hashes.contains_key("product:2"));
+        assert!(!redis.sorted_sets.contains_key("product:2:sales"));
+        assert!(!redis.sorted_sets["product:1:sales"].contains_key("c"));
+        assert_eq!(redis.sets[SALES].len(), 1);
+    }
+
+    #[test]
+    fn sync_refuses_to_delete_a_product_a_kept_sale_refers_to() {
+        let mut store = loaded();
+        let input = SalesAndProducts::new(vec![product(1, "apple")], vec![sale("a", 1, 1_000), sale("b", 2, 2_000)]);
+        let error = store::load(&mut store, &input, LoadMode::Sync).unwrap_err();
+
+        assert_eq!(error, "cannot delete product 2, sale b refers to it");
+        assert_eq!(store::query(&mut store, &DateRange::default()).unwrap(), sample());
+    }
+
+    #[test]
+    fn sync_deletes_a_product_whose_sales_moved_away() {
+        let mut store = loaded();
+        let input = SalesAndProducts::new(vec![product(1, "apple")], vec![sale("b", 1, 2_000)]);
+        let report = store::load(&mut store, &input, LoadMode::Sync).unwrap();
+
+        assert_eq!((report.products.deleted, report.sales.deleted), (1, 2));
+        assert_eq!(store::query(&mut store, &DateRange::default()).unwrap(), input);
+    }
+}
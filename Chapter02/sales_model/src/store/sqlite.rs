@@ -0,0 +1,269 @@
+use super::{Migration, SalesStore};
+use crate::load::RowChange;
+use crate::{quantity, DateRange, Product, Sale, Timestamp};
+use rusqlite::types::{FromSql, ToSql};
+use rusqlite::{params, Connection};
+use std::collections::HashSet;
+use std::hash::Hash;
+use std::path::Path;
+
+// The first migrations use `IF NOT EXISTS` so databases created before
+// versioning are adopted instead of failing.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "create_products",
+        up: include_str!("../../migrations/sqlite/0001_create_products.up.sql"),
+        down: include_str!("../../migrations/sqlite/0001_create_products.down.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "create_sales",
+        up: include_str!("../../migrations/sqlite/0002_create_sales.up.sql"),
+        down: include_str!("../../migrations/sqlite/0002_create_sales.down.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "sales_quantity_text",
+        up: include_str!("../../migrations/sqlite/0003_sales_quantity_text.up.sql"),
+        down: include_str!("../../migrations/sqlite/0003_sales_quantity_text.down.sql"),
+    },
+];
+
+pub struct SqliteStore {
+    conn: Connection,
+}
+
+impl SqliteStore {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let conn = Connection::open(path).map_err(|e| e.to_string())?;
+        SqliteStore::from_connection(conn)
+    }
+
+    pub fn open_in_memory() -> Result<Self, String> {
+        let conn = Connection::open_in_memory().map_err(|e| e.to_string())?;
+        SqliteStore::from_connection(conn)
+    }
+
+    fn from_connection(conn: Connection) -> Result<Self, String> {
+        conn.execute_batch("PRAGMA foreign_keys = ON").map_err(|e| e.to_string())?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS schema_version (
+                  version         INTEGER PRIMARY KEY,
+                  name            TEXT NOT NULL,
+                  applied_at      TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+                  )",
+            [],
+        )
+        .map_err(|e| e.to_string())?;
+        Ok(SqliteStore { conn })
+    }
+
+    pub fn connection(&mut self) -> &mut Connection {
+        &mut self.conn
+    }
+
+    fn row_exists(&self, table: &str, id: &dyn ToSql) -> Result<bool, String> {
+        self.conn
+            .query_row(
+                &format!("SELECT EXISTS(SELECT 1 FROM {} WHERE id = ?1)", table),
+                [id],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())
+    }
+
+    fn delete_missing<T>(&self, table: &str, keep: &HashSet<T>) -> Result<usize, String>
+    where
+        T: FromSql + ToSql + Eq + Hash,
+    {
+        let mut stmt = self
+            .conn
+            .prepare(&format!("SELECT id FROM {}", table))
+            .map_err(|e| e.to_string())?;
+        let ids = stmt
+            .query_map([], |row| row.get::<_, T>(0))
+            .and_then(|rows| rows.collect::<Result<Vec<T>, _>>())
+            .map_err(|e| e.to_string())?;
+
+        let mut deleted = 0;
+        for id in ids.iter().filter(|id| !keep.contains(id)) {
+            deleted += self
+                .conn
+                .execute(&format!("DELETE FROM {} WHERE id = ?1", table), [id])
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(deleted)
+    }
+}
+
+fn row_change(existed: bool, changed_rows: usize) -> RowChange {
+    match (existed, changed_rows) {
+        (false, _) => RowChange::Inserted,
+        (true, 0) => RowChange::Unchanged,
+        (true, _) => RowChange::Updated,
+    }
+}
+
+impl SalesStore for SqliteStore {
+    fn migrations(&self) -> &'static [Migration] {
+        MIGRATIONS
+    }
+
+    fn schema_version(&mut self) -> Result<u32, String> {
+        self.conn
+            .query_row("SELECT COALESCE(MAX(version), 0) FROM schema_version", [], |row| row.get(0))
+            .map_err(|e| e.to_string())
+    }
+
+    fn apply_migration(&mut self, migration: &Migration, up: bool) -> Result<(), String> {
+        let tx = self.conn.transaction().map_err(|e| e.to_string())?;
+        if up {
+            tx.execute_batch(migration.up).map_err(|e| e.to_string())?;
+            tx.execute(
+                "INSERT INTO schema_version (version, name) VALUES (?1, ?2)",
+                params![migration.version, migration.name],
+            )
+            .map_err(|e| e.to_string())?;
+        } else {
+            tx.execute_batch(migration.down).map_err(|e| e.to_string())?;
+            tx.execute(
+                "DELETE FROM schema_version WHERE version = ?1",
+                params![migration.version],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        tx.commit().map_err(|e| e.to_string())
+    }
+
+    fn begin(&mut self) -> Result<(), String> {
+        self.conn.execute_batch("BEGIN").map_err(|e| e.to_string())
+    }
+
+    fn commit(&mut self) -> Result<(), String> {
+        self.conn.execute_batch("COMMIT").map_err(|e| e.to_string())
+    }
+
+    fn rollback(&mut self) -> Result<(), String> {
+        self.conn.execute_batch("ROLLBACK").map_err(|e| e.to_string())
+    }
+
+    fn insert_product(&mut self, product: &Product) -> Result<(), String> {
+        self.conn
+            .execute(
+                "INSERT INTO products (id, category, name) VALUES (?1, ?2, ?3)",
+                params![product.id, product.category, product.name],
+            )
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    fn insert_sale(&mut self, sale: &Sale) -> Result<(), String> {
+        self.conn
+            .execute(
+                "INSERT INTO sales (id, product_id, date, quantity, unit) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    sale.id,
+                    sale.product_id,
+                    sale.date.epoch(),
+                    sale.quantity.to_string(),
+                    sale.unit.symbol()
+                ],
+            )
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    fn upsert_product(&mut self, product: &Product) -> Result<RowChange, String> {
+        let exists = self.row_exists("products", &product.id)?;
+        let changed = self
+            .conn
+            .execute(
+                "INSERT INTO products (id, category, name) VALUES (?1, ?2, ?3)
+                    ON CONFLICT(id) DO UPDATE SET category = excluded.category, name = excluded.name
+                    WHERE category IS NOT excluded.category OR name IS NOT excluded.name",
+                params![product.id, product.category, product.name],
+            )
+            .map_err(|e| e.to_string())?;
+        Ok(row_change(exists, changed))
+    }
+
+    fn upsert_sale(&mut self, sale: &Sale) -> Result<RowChange, String> {
+        let exists = self.row_exists("sales", &sale.id)?;
+        let changed = self
+            .conn
+            .execute(
+                "INSERT INTO sales (id, product_id, date, quantity, unit) VALUES (?1, ?2, ?3, ?4, ?5)
+                    ON CONFLICT(id) DO UPDATE SET product_id = excluded.product_id, date = excluded.date,
+                        quantity = excluded.quantity, unit = excluded.unit
+                    WHERE product_id IS NOT excluded.product_id OR date IS NOT excluded.date
+                        OR quantity IS NOT excluded.quantity OR unit IS NOT excluded.unit",
+                params![
+                    sale.id,
+                    sale.product_id,
+                    sale.date.epoch(),
+                    sale.quantity.to_string(),
+                    sale.unit.symbol()
+                ],
+            )
+            .map_err(|e| e.to_string())?;
+        Ok(row_change(exists, changed))
+    }
+
+    fn delete_products_except(&mut self, keep: &HashSet<i32>) -> Result<usize, String> {
+        self.delete_missing("products", keep)
+    }
+
+    fn delete_sales_except(&mut self, keep: &HashSet<String>) -> Result<usize, String> {
+        self.delete_missing("sales", keep)
+    }
+
+    fn products(&mut self) -> Result<Vec<Product>, String> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, category, name FROM products ORDER BY id")
+            .map_err(|e| e.to_string())?;
+        let product_iter = stmt
+            .query_map([], |row| {
+                Ok(Product {
+                    id: row.get(0)?,
+                    category: row.get(1)?,
+                    name: row.get(2)?,
+                })
+            })
+            .map_err(|e| e.to_string())?;
+        product_iter.collect::<Result<_, _>>().map_err(|e| e.to_string())
+    }
+
+    fn sales(&mut self, range: &DateRange) -> Result<Vec<Sale>, String> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT id, product_id, date, quantity, unit FROM sales
+                    WHERE (?1 IS NULL OR date >= ?1) AND (?2 IS NULL OR date < ?2)
+                    ORDER BY id",
+            )
+            .map_err(|e| e.to_string())?;
+        let from = range.from.map(|from| from.epoch());
+        let to = range.to.map(|to| to.epoch());
+        let sale_iter = stmt
+            .query_map(params![from, to], |row| {
+                Ok(Sale {
+                    id: row.get(0)?,
+                    product_id: row.get(1)?,
+                    // Dates are stored as epoch seconds
+                    date: Timestamp::from_epoch(row.get(2)?).map_err(|e| {
+                        rusqlite::Error::FromSqlConversionFailure(2, rusqlite::types::Type::Integer, e.into())
+                    })?,
+                    quantity: quantity::parse(&row.get::<_, String>(3)?).map_err(|e| {
+                        rusqlite::Error::FromSqlConversionFailure(3, rusqlite::types::Type::Text, e.into())
+                    })?,
+                    unit: row.get::<_, String>(4)?.parse().map_err(|e: String| {
+                        rusqlite::Error::FromSqlConversionFailure(4, rusqlite::types::Type::Text, e.into())
+                    })?,
+                })
+            })
+            .map_err(|e| e.to_string())?;
+        sale_iter.collect::<Result<_, _>>().map_err(|e| e.to_string())
+    }
+}
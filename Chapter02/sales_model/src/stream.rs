@@ -0,0 +1,402 @@
+use crate::{Product, Sale};
+use serde::de::{self, DeserializeOwned};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::value::RawValue;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+/// A product or a sale. In JSON Lines files each record is tagged with its
+/// kind, as in `{"type":"sale","id":"2020-3987",...}`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Record<S = Sale> {
+    Product(Product),
+    Sale(S),
+}
+
+#[derive(Deserialize)]
+struct Tag {
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+// Tagged records only come from JSON. They are parsed twice, for the tag and
+// then for the record, since serde reads an internally tagged enum through a
+// buffer that keeps numbers as floats, and quantities need their digits
+impl<'de, S: DeserializeOwned> Deserialize<'de> for Record<S> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = Box::<RawValue>::deserialize(deserializer)?;
+        let tag: Tag = serde_json::from_str(raw.get()).map_err(de::Error::custom)?;
+        match tag.kind.as_str() {
+            "product" => serde_json::from_str(raw.get()).map(Record::Product),
+            "sale" => serde_json::from_str(raw.get()).map(Record::Sale),
+            kind => return Err(de::Error::unknown_variant(kind, &["product", "sale"])),
+        }
+        .map_err(de::Error::custom)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    Start,
+    /// Between the members of the document object
+    Members,
+    Products,
+    Sales,
+    Done,
+}
+
+/// Reads the records of a sales JSON document one at a time, holding only
+/// the record being parsed in memory. Records come in file order, so the
+/// `products` array should come before `sales` when loading into a database
+/// that checks product references. Other members are skipped, and the
+/// iteration stops at the first error.
+pub struct JsonRecords<R> {
+    reader: R,
+    state: State,
+    /// Bytes consumed so far, to locate errors
+    offset: usize,
+    /// Index of the next element of the current array
+    index: usize,
+    buf: Vec<u8>,
+}
+
+pub fn json_records<P: AsRef<Path>>(input_path: P) -> Result<JsonRecords<BufReader<File>>, String> {
+    let path = input_path.as_ref();
+    let file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(JsonRecords::new(BufReader::new(file)))
+}
+
+impl<R: BufRead> JsonRecords<R> {
+    pub fn new(reader: R) -> Self {
+        JsonRecords {
+            reader,
+            state: State::Start,
+            offset: 0,
+            index: 0,
+            buf: vec![],
+        }
+    }
+
+    fn peek(&mut self) -> Result<Option<u8>, String> {
+        let available = self.reader.fill_buf().map_err(|e| e.to_string())?;
+        Ok(available.first().copied())
+    }
+
+    fn next_byte(&mut self) -> Result<Option<u8>, String> {
+        let byte = self.peek()?;
+        if byte.is_some() {
+            self.reader.consume(1);
+            self.offset += 1;
+        }
+        Ok(byte)
+    }
+
+    /// The next byte that is not whitespace, without consuming it.
+    fn peek_token(&mut self) -> Result<Option<u8>, String> {
+        while let Some(byte) = self.peek()? {
+            if !byte.is_ascii_whitespace() {
+                return Ok(Some(byte));
+            }
+            self.next_byte()?;
+        }
+        Ok(None)
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<(), String> {
+        match self.peek_token()? {
+            Some(byte) if byte == expected => {
+                self.next_byte()?;
+                Ok(())
+            }
+            found => Err(self.unexpected(found, &format!("`{}`", expected as char))),
+        }
+    }
+
+    fn unexpected(&self, found: Option<u8>, expected: &str) -> String {
+        match found {
+            Some(byte) => format!("byte {}: expected {}, found `{}`", self.offset, expected, byte as char),
+            None => format!("byte {}: expected {}, found the end of the file", self.offset, expected),
+        }
+    }
+
+    /// Copies the next complete JSON value into `buf`, without parsing it
+    /// beyond matching brackets and strings.
+    fn read_value(&mut self) -> Result<(), String> {
+        self.buf.clear();
+        let start = self.offset;
+        let mut depth = 0usize;
+        let mut in_string = false;
+        let mut escaped = false;
+
+        match self.peek_token()? {
+            Some(b',' | b']' | b'}' | b':') | None => {
+                let found = self.peek()?;
+                return Err(self.unexpected(found, "a value"));
+            }
+            _ => {}
+        }
+
+        loop {
+            let byte = match self.peek()? {
+                Some(byte) => byte,
+                None if depth == 0 && !in_string => break,
+                None => return Err(format!("byte {}: value is not terminated", start)),
+            };
+            if in_string {
+                self.next_byte()?;
+                self.buf.push(byte);
+                match byte {
+                    _ if escaped => escaped = false,
+                    b'\\' => escaped = true,
+                    b'"' => {
+                        in_string = false;
+                        if depth == 0 {
+                            break;
+                        }
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+            match byte {
+                b'"' => in_string = true,
+                b'{' | b'[' => depth += 1,
+                b'}' | b']' if depth == 0 => break,
+                b'}' | b']' => depth -= 1,
+                b',' if depth == 0 => break,
+                byte if byte.is_ascii_whitespace() && depth == 0 => break,
+                _ => {}
+            }
+            self.next_byte()?;
+            self.buf.push(byte);
+            if depth == 0 && matches!(byte, b'}' | b']') {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn parse<T: DeserializeOwned>(&self, name: &str) -> Result<T, String> {
+        serde_json::from_slice(&self.buf).map_err(|e| format!("{}[{}]: {}", name, self.index, e))
+    }
+
+    /// Moves to the next array of records, or to the end of the document.
+    fn next_member(&mut self, first: bool) -> Result<(), String> {
+        if !first {
+            match self.peek_token()? {
+                Some(b',') => {
+                    self.next_byte()?;
+                }
+                Some(b'}') => {
+                    self.next_byte()?;
+                    self.state = State::Done;
+                    return match self.peek_token()? {
+                        None => Ok(()),
+                        found => Err(self.unexpected(found, "the end of the file")),
+                    };
+                }
+                found => return Err(self.unexpected(found, "`,` or `}`")),
+            }
+        } else if self.peek_token()? == Some(b'}') {
+            self.next_byte()?;
+            self.state = State::Done;
+            return Ok(());
+        }
+
+        loop {
+            if self.peek_token()? != Some(b'"') {
+                let found = self.peek()?;
+                return Err(self.unexpected(found, "a member name"));
+            }
+            self.read_value()?;
+            let name: String = serde_json::from_slice(&self.buf).map_err(|e| format!("byte {}: {}", self.offset, e))?;
+            self.expect(b':')?;
+
+            let state = match name.as_str() {
+                "products" => State::Products,
+                "sales" => State::Sales,
+                _ => State::Members,
+            };
+            if state == State::Members {
+                self.read_value()?;
+                match self.peek_token()? {
+                    Some(b',') => {
+                        self.next_byte()?;
+                        continue;
+                    }
+                    Some(b'}') => return self.next_member(false),
+                    found => return Err(self.unexpected(found, "`,` or `}`")),
+                }
+            }
+
+            self.expect(b'[')?;
+            self.state = state;
+            self.index = 0;
+            return Ok(());
+        }
+    }
+
+    fn next_record(&mut self) -> Result<Option<Record>, String> {
+        loop {
+            match self.state {
+                State::Start => {
+                    self.expect(b'{')?;
+                    self.next_member(true)?;
+                }
+                State::Members => self.next_member(false)?,
+                State::Products | State::Sales => {
+                    if self.index > 0 {
+                        match self.peek_token()? {
+                            Some(b',') => {
+                                self.next_byte()?;
+                            }
+                            Some(b']') => {}
+                            found => return Err(self.unexpected(found, "`,` or `]`")),
+                        }
+                    }
+                    if self.peek_token()? == Some(b']') {
+                        self.next_byte()?;
+                        self.state = State::Members;
+                        continue;
+                    }
+
+                    self.read_value()?;
+                    let record = if self.state == State::Products {
+                        Record::Product(self.parse("products")?)
+                    } else {
+                        Record::Sale(self.parse("sales")?)
+                    };
+                    self.index += 1;
+                    return Ok(Some(record));
+                }
+                State::Done => return Ok(None),
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for JsonRecords<R> {
+    type Item = Result<Record, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_record() {
+            Ok(record) => record.map(Ok),
+            Err(e) => {
+                self.state = State::Done;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Decimal, Unit};
+
+    const SALE: &str = r#"{"id": "a", "product_id": 1, "date": 0, "quantity": 2.5, "unit": "Kg"}"#;
+    const PRODUCT: &str = r#"{"id": 1, "category": "fruit", "name": "apple"}"#;
+
+    // A tiny buffer, so values are split across reads
+    fn records(content: &str) -> Result<Vec<Record>, String> {
+        JsonRecords::new(BufReader::with_capacity(3, content.as_bytes())).collect()
+    }
+
+    fn kinds(records: &[Record]) -> Vec<&str> {
+        records
+            .iter()
+            .map(|record| match record {
+                Record::Product(_) => "product",
+                Record::Sale(_) => "sale",
+            })
+            .collect()
+    }
+
+    #[test]
+    fn records_come_in_file_order() {
+        let content = format!(r#"{{"products": [{}, {}], "sales": [{}]}}"#, PRODUCT, PRODUCT, SALE);
+        let read = records(&content).unwrap();
+        assert_eq!(kinds(&read), ["product", "product", "sale"]);
+        assert_eq!(read[0], Record::Product(Product::new(1, "fruit", "apple")));
+        match &read[2] {
+            Record::Sale(sale) => {
+                assert_eq!(sale.quantity, Decimal::new(25, 1));
+                assert_eq!(sale.unit, Unit::Kilogram);
+            }
+            record => panic!("expected a sale, found {:?}", record),
+        }
+
+        let content = format!(r#"{{"sales": [{}], "products": [{}]}}"#, SALE, PRODUCT);
+        assert_eq!(kinds(&records(&content).unwrap()), ["sale", "product"]);
+    }
+
+    #[test]
+    fn empty_and_missing_arrays_give_no_records() {
+        assert_eq!(records("{}").unwrap(), []);
+        assert_eq!(records(" \n{ \"products\" : [ ] ,\"sales\":[]}\n").unwrap(), []);
+        assert_eq!(kinds(&records(&format!(r#"{{"sales": [{}]}}"#, SALE)).unwrap()), ["sale"]);
+    }
+
+    #[test]
+    fn other_members_are_skipped_whatever_they_hold() {
+        let content = format!(
+            r#"{{
+                "version": 2,
+                "meta": {{"tags": ["a]", "b}}", {{"c": [1, [2]]}}], "quote": "\"]}},", "empty": {{}}}},
+                "products": [{}],
+                "flag": true,
+                "note": null,
+                "sales": [{}],
+                "trailer": "x"
+            }}"#,
+            PRODUCT, SALE
+        );
+        assert_eq!(kinds(&records(&content).unwrap()), ["product", "sale"]);
+    }
+
+    #[test]
+    fn strings_in_records_may_hold_brackets_and_escapes() {
+        let content = r#"{"products": [{"id": 1, "category": "a, [b]", "name": "say \"hi\" }\\"}]}"#;
+        assert_eq!(records(content).unwrap(), [Record::Product(Product::new(1, "a, [b]", "say \"hi\" }\\"))]);
+    }
+
+    #[test]
+    fn malformed_documents_give_the_byte_offset() {
+        assert_eq!(records("[]").unwrap_err(), "byte 0: expected `{`, found `[`");
+        assert_eq!(records("").unwrap_err(), "byte 0: expected `{`, found the end of the file");
+        assert_eq!(records(r#"{"products" []}"#).unwrap_err(), "byte 12: expected `:`, found `[`");
+        assert_eq!(records(r#"{"products": {}}"#).unwrap_err(), "byte 13: expected `[`, found `{`");
+        assert_eq!(records(r#"{products: []}"#).unwrap_err(), "byte 1: expected a member name, found `p`");
+        assert_eq!(
+            records(&format!(r#"{{"products": [{} {}]}}"#, PRODUCT, PRODUCT)).unwrap_err(),
+            "byte 62: expected `,` or `]`, found `{`"
+        );
+        assert_eq!(records(r#"{"products": [,]}"#).unwrap_err(), "byte 14: expected a value, found `,`");
+        assert_eq!(records(r#"{"products": []"#).unwrap_err(), "byte 15: expected `,` or `}`, found the end of the file");
+        assert_eq!(records(r#"{"sales": []} []"#).unwrap_err(), "byte 14: expected the end of the file, found `[`");
+        assert_eq!(records(r#"{"sales": [{"id": "a}]}"#).unwrap_err(), "byte 11: value is not terminated");
+    }
+
+    #[test]
+    fn invalid_records_give_their_array_and_index() {
+        let content = format!(r#"{{"products": [{}, {{"id": "x"}}]}}"#, PRODUCT);
+        let e = records(&content).unwrap_err();
+        assert!(e.starts_with("products[1]: invalid type: string \"x\", expected i32"), "{}", e);
+
+        let content = r#"{"sales": [{"id": "a", "product_id": 1, "date": 0, "quantity": 1, "unit": "oz"}]}"#;
+        let e = records(content).unwrap_err();
+        assert!(e.starts_with("sales[0]: "), "{}", e);
+        assert!(e.contains("oz"), "{}", e);
+    }
+
+    #[test]
+    fn iteration_stops_at_the_first_error() {
+        let content = format!(r#"{{"products": [{}, 5, {}]}}"#, PRODUCT, PRODUCT);
+        let mut read = JsonRecords::new(content.as_bytes());
+        assert!(read.next().unwrap().is_ok());
+        assert!(read.next().unwrap().is_err());
+        assert!(read.next().is_none());
+    }
+}
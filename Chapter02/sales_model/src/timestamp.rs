@@ -0,0 +1,338 @@
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, Offset, SecondsFormat, SubsecRound, TimeZone, Utc};
+use serde::de::{self, Visitor};
+use serde::ser::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::{self, Write};
+use std::str::FromStr;
+
+/// How timestamps are written by [`WithFormat`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum TimestampFormat {
+    /// Seconds since 1970-01-01 UTC, as the sales files have always used
+    #[default]
+    Epoch,
+    /// RFC 3339 in UTC, as in `2009-02-13T23:31:30Z`
+    Rfc3339,
+    /// A chrono `strftime` pattern, applied in UTC. Timestamps written this
+    /// way are read back only if the pattern is ISO 8601
+    Pattern(String),
+}
+
+impl TimestampFormat {
+    pub fn serialize<S: Serializer>(&self, timestamp: &Timestamp, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            TimestampFormat::Epoch => serializer.serialize_i64(timestamp.epoch()),
+            TimestampFormat::Rfc3339 => serializer.serialize_str(&timestamp.to_string()),
+            TimestampFormat::Pattern(pattern) => {
+                // `to_string` would panic on a specifier chrono cannot format
+                let mut date = String::new();
+                write!(date, "{}", timestamp.0.format(pattern))
+                    .map_err(|_| S::Error::custom(format!("invalid date format `{}`", pattern)))?;
+                serializer.serialize_str(&date)
+            }
+        }
+    }
+}
+
+impl fmt::Display for TimestampFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimestampFormat::Epoch => f.write_str("epoch"),
+            TimestampFormat::Rfc3339 => f.write_str("rfc3339"),
+            TimestampFormat::Pattern(pattern) => f.write_str(pattern),
+        }
+    }
+}
+
+impl FromStr for TimestampFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "epoch" => Ok(TimestampFormat::Epoch),
+            "rfc3339" | "iso8601" | "iso" => Ok(TimestampFormat::Rfc3339),
+            _ if s.contains('%') => {
+                if StrftimeItems::new(s).any(|item| item == Item::Error) {
+                    return Err(format!("invalid date format `{}`, not a valid strftime pattern", s));
+                }
+                Ok(TimestampFormat::Pattern(s.to_string()))
+            }
+            _ => Err(format!(
+                "unknown date format `{}`, expected epoch, rfc3339 or a strftime pattern",
+                s
+            )),
+        }
+    }
+}
+
+/// The date of a sale, an instant with second precision. It is read from
+/// epoch seconds or ISO 8601 strings and written as epoch seconds, or in
+/// another [`TimestampFormat`] through [`WithFormat`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp(DateTime<Utc>);
+
+impl Timestamp {
+    pub fn from_epoch(seconds: i64) -> Result<Timestamp, String> {
+        DateTime::from_timestamp(seconds, 0)
+            .map(Timestamp)
+            .ok_or_else(|| format!("timestamp {} is out of range", seconds))
+    }
+
+    pub fn epoch(&self) -> i64 {
+        self.0.timestamp()
+    }
+
+    pub fn datetime(&self) -> DateTime<Utc> {
+        self.0
+    }
+
+    /// Parses an ISO 8601 date or date and time, or epoch seconds. Values
+    /// without an offset are taken to be in `offset`; fractions of a second
+    /// are dropped. ISO 8601 is tried first, so `2020` is the start of that
+    /// year rather than 2020 seconds after the epoch.
+    pub fn parse_in(s: &str, offset: FixedOffset) -> Result<Timestamp, String> {
+        let s = s.trim();
+        if let Ok(datetime) = DateTime::parse_from_rfc3339(s) {
+            return Timestamp::from_epoch(datetime.timestamp());
+        }
+        // RFC 3339 requires seconds and a `T`, ISO 8601 does not
+        let zoned = match s.strip_suffix(['Z', 'z']) {
+            Some(local) => format!("{}+00:00", local),
+            None => s.to_string(),
+        };
+        let datetime = ["%Y-%m-%dT%H:%M:%S%.f%:z", "%Y-%m-%d %H:%M:%S%.f%:z", "%Y-%m-%dT%H:%M%:z", "%Y-%m-%d %H:%M%:z"]
+            .iter()
+            .find_map(|pattern| DateTime::parse_from_str(&zoned, pattern).ok());
+        if let Some(datetime) = datetime {
+            return Timestamp::from_epoch(datetime.timestamp());
+        }
+        let naive = ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M"]
+            .iter()
+            .find_map(|pattern| NaiveDateTime::parse_from_str(s, pattern).ok())
+            .or_else(|| {
+                // ISO 8601 also allows a year, or a year and month, alone
+                let date = match s.len() {
+                    4 => format!("{}-01-01", s),
+                    7 => format!("{}-01", s),
+                    _ => s.to_string(),
+                };
+                NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+                    .ok()
+                    .and_then(|date| date.and_hms_opt(0, 0, 0))
+            });
+        let naive = match naive {
+            Some(naive) => naive,
+            None => {
+                let seconds = s
+                    .parse::<i64>()
+                    .map_err(|_| format!("invalid date `{}`, expected ISO 8601 or epoch seconds", s))?;
+                return Timestamp::from_epoch(seconds);
+            }
+        };
+        match offset.from_local_datetime(&naive).single() {
+            Some(datetime) => Timestamp::from_epoch(datetime.timestamp()),
+            None => Err(format!("invalid date `{}` in {}", s, offset)),
+        }
+    }
+}
+
+impl From<DateTime<Utc>> for Timestamp {
+    fn from(datetime: DateTime<Utc>) -> Self {
+        Timestamp(datetime.trunc_subsecs(0))
+    }
+}
+
+impl Default for Timestamp {
+    fn default() -> Self {
+        Timestamp(DateTime::UNIX_EPOCH)
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0.to_rfc3339_opts(SecondsFormat::Secs, true))
+    }
+}
+
+impl FromStr for Timestamp {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Timestamp::parse_in(s, Utc.fix())
+    }
+}
+
+impl Serialize for Timestamp {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i64(self.epoch())
+    }
+}
+
+/// Serializes `value` with its timestamps in `format`, e.g.
+/// `WithFormat::new(&data, &TimestampFormat::Rfc3339)` for a whole dataset.
+pub struct WithFormat<'a, T: ?Sized> {
+    pub value: &'a T,
+    pub format: &'a TimestampFormat,
+}
+
+impl<'a, T: ?Sized> WithFormat<'a, T> {
+    pub fn new(value: &'a T, format: &'a TimestampFormat) -> Self {
+        WithFormat { value, format }
+    }
+}
+
+impl Serialize for WithFormat<'_, Timestamp> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.format.serialize(self.value, serializer)
+    }
+}
+
+struct TimestampVisitor;
+
+impl<'de> Visitor<'de> for TimestampVisitor {
+    type Value = Timestamp;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("epoch seconds or an ISO 8601 date")
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Timestamp, E> {
+        Timestamp::from_epoch(value).map_err(E::custom)
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Timestamp, E> {
+        let value = i64::try_from(value).map_err(E::custom)?;
+        self.visit_i64(value)
+    }
+
+    // Formats such as XML and CSV hold epoch seconds as text, so integers
+    // are epoch seconds here even when they could be a year
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Timestamp, E> {
+        match value.trim().parse::<i64>() {
+            Ok(seconds) => self.visit_i64(seconds),
+            Err(_) => value.parse().map_err(E::custom),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Timestamp {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(TimestampVisitor)
+    }
+}
+
+/// Dates from `from`, inclusive, to `to`, exclusive. Missing bounds are open.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DateRange {
+    pub from: Option<Timestamp>,
+    pub to: Option<Timestamp>,
+}
+
+impl DateRange {
+    /// Parses both bounds with [`Timestamp::parse_in`].
+    pub fn parse(from: Option<&str>, to: Option<&str>, offset: FixedOffset) -> Result<DateRange, String> {
+        let range = DateRange {
+            from: from.map(|from| Timestamp::parse_in(from, offset)).transpose()?,
+            to: to.map(|to| Timestamp::parse_in(to, offset)).transpose()?,
+        };
+        if let (Some(from), Some(to)) = (range.from, range.to) {
+            if from >= to {
+                return Err(format!("empty date range, {} is not before {}", from, to));
+            }
+        }
+        Ok(range)
+    }
+
+    pub fn is_all(&self) -> bool {
+        self.from.is_none() && self.to.is_none()
+    }
+
+    pub fn contains(&self, date: Timestamp) -> bool {
+        self.from.is_none_or(|from| date >= from) && self.to.is_none_or(|to| date < to)
+    }
+}
+
+/// Parses a fixed UTC offset such as `+02:00`, or `UTC`/`Z`. Time zone
+/// names such as `Europe/Madrid` are not supported, as their offset changes
+/// with daylight saving time.
+pub fn parse_offset(s: &str) -> Result<FixedOffset, String> {
+    match s {
+        "UTC" | "utc" | "Z" | "z" => Ok(Utc.fix()),
+        _ => s
+            .parse()
+            .map_err(|_| format!("invalid UTC offset `{}`, expected UTC or +HH:MM", s)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(s: &str) -> i64 {
+        Timestamp::parse_in(s, Utc.fix()).unwrap().epoch()
+    }
+
+    #[test]
+    fn iso_dates_are_tried_before_epoch_seconds() {
+        assert_eq!(utc("2020"), 1_577_836_800);
+        assert_eq!(utc("2020-02"), 1_580_515_200);
+        assert_eq!(utc("2020-02-01"), 1_580_515_200);
+        assert_eq!(utc("20200"), 20_200);
+        assert_eq!(utc("1234567890"), 1_234_567_890);
+        assert_eq!(utc("-60"), -60);
+    }
+
+    #[test]
+    fn dates_without_an_offset_are_in_the_given_one() {
+        let madrid = parse_offset("+02:00").unwrap();
+        let epoch = |s: &str| Timestamp::parse_in(s, madrid).unwrap().epoch();
+        assert_eq!(epoch("2020-06-01"), 1_590_962_400);
+        assert_eq!(epoch("2020-06-01 02:00"), 1_590_969_600);
+        assert_eq!(epoch("2020-06-01T00:00:00Z"), 1_590_969_600);
+        assert_eq!(epoch("2020-06-01T00:00:00.750+00:00"), 1_590_969_600);
+    }
+
+    #[test]
+    fn invalid_dates_and_offsets_are_rejected() {
+        assert_eq!(
+            Timestamp::parse_in("2020-13", Utc.fix()).unwrap_err(),
+            "invalid date `2020-13`, expected ISO 8601 or epoch seconds"
+        );
+        assert!(Timestamp::parse_in("01/02/2020", Utc.fix()).is_err());
+        assert!(parse_offset("Europe/Madrid").is_err());
+        assert_eq!(parse_offset("Z").unwrap(), Utc.fix());
+    }
+
+    #[test]
+    fn integers_in_files_are_epoch_seconds() {
+        let read = |json: &str| serde_json::from_str::<Timestamp>(json).unwrap().epoch();
+        assert_eq!(read("2020"), 2020);
+        assert_eq!(read("\"2020\""), 2020);
+        assert_eq!(read("\"2020-01-01\""), 1_577_836_800);
+    }
+
+    #[test]
+    fn timestamps_are_written_in_the_given_format() {
+        let timestamp = Timestamp::from_epoch(1_234_567_890).unwrap();
+        let json = |format: TimestampFormat| serde_json::to_string(&WithFormat::new(&timestamp, &format)).unwrap();
+        assert_eq!(serde_json::to_string(&timestamp).unwrap(), "1234567890");
+        assert_eq!(json(TimestampFormat::Epoch), "1234567890");
+        assert_eq!(json(TimestampFormat::Rfc3339), "\"2009-02-13T23:31:30Z\"");
+        assert_eq!(json("%d/%m/%Y".parse().unwrap()), "\"13/02/2009\"");
+    }
+
+    #[test]
+    fn invalid_patterns_are_rejected() {
+        assert_eq!(
+            "%Q".parse::<TimestampFormat>().unwrap_err(),
+            "invalid date format `%Q`, not a valid strftime pattern"
+        );
+        assert!("%Y-%".parse::<TimestampFormat>().is_err());
+
+        let timestamp = Timestamp::from_epoch(0).unwrap();
+        let format = TimestampFormat::Pattern("%Q".to_string());
+        let e = serde_json::to_string(&WithFormat::new(&timestamp, &format)).unwrap_err();
+        assert_eq!(e.to_string(), "invalid date format `%Q`");
+    }
+}
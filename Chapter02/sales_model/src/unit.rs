@@ -0,0 +1,161 @@
+use rust_decimal::Decimal;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Dimension {
+    Count,
+    Mass,
+    Volume,
+}
+
+impl Dimension {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Dimension::Count => "count",
+            Dimension::Mass => "mass",
+            Dimension::Volume => "volume",
+        }
+    }
+
+    /// The unit quantities of this dimension are converted to when added up.
+    pub fn base_unit(&self) -> Unit {
+        match self {
+            Dimension::Count => Unit::Piece,
+            Dimension::Mass => Unit::Kilogram,
+            Dimension::Volume => Unit::Litre,
+        }
+    }
+}
+
+impl fmt::Display for Dimension {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Unit of measure of a sale quantity. It is written with the symbols the
+/// sales files have always used (`u.`, `Kg`, ...) and read from any of the
+/// spellings accepted by [`Unit::from_str`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Unit {
+    #[default]
+    Piece,
+    Kilogram,
+    Gram,
+    Pound,
+    Litre,
+    Millilitre,
+}
+
+impl Unit {
+    pub const ALL: &'static [Unit] = &[
+        Unit::Piece,
+        Unit::Kilogram,
+        Unit::Gram,
+        Unit::Pound,
+        Unit::Litre,
+        Unit::Millilitre,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Unit::Piece => "piece",
+            Unit::Kilogram => "kilogram",
+            Unit::Gram => "gram",
+            Unit::Pound => "pound",
+            Unit::Litre => "litre",
+            Unit::Millilitre => "millilitre",
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Unit::Piece => "u.",
+            Unit::Kilogram => "Kg",
+            Unit::Gram => "g",
+            Unit::Pound => "lb",
+            Unit::Litre => "L",
+            Unit::Millilitre => "ml",
+        }
+    }
+
+    pub fn dimension(&self) -> Dimension {
+        match self {
+            Unit::Piece => Dimension::Count,
+            Unit::Kilogram | Unit::Gram | Unit::Pound => Dimension::Mass,
+            Unit::Litre | Unit::Millilitre => Dimension::Volume,
+        }
+    }
+
+    // How many base units of the dimension one of this unit is
+    fn factor(&self) -> Decimal {
+        match self {
+            Unit::Piece | Unit::Kilogram | Unit::Litre => Decimal::ONE,
+            Unit::Gram | Unit::Millilitre => Decimal::new(1, 3),
+            Unit::Pound => Decimal::new(45_359_237, 8),
+        }
+    }
+
+    /// Converts `quantity` of this unit to `to`, which must measure the same
+    /// dimension. The result is exact but for conversions from metric units
+    /// to pounds, which have as many digits as a decimal holds.
+    pub fn convert(&self, quantity: Decimal, to: Unit) -> Result<Decimal, String> {
+        if self.dimension() != to.dimension() {
+            return Err(format!(
+                "cannot convert {} ({}) to {} ({})",
+                self,
+                self.dimension(),
+                to,
+                to.dimension()
+            ));
+        }
+        if *self == to {
+            return Ok(quantity);
+        }
+        quantity
+            .checked_mul(self.factor())
+            .and_then(|base| base.checked_div(to.factor()))
+            .ok_or_else(|| format!("{} {} is too large to convert to {}", quantity, self, to))
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.symbol())
+    }
+}
+
+impl FromStr for Unit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let unit = match s.trim() {
+            "u." | "u" | "unit" | "units" | "pc" | "pcs" => Unit::Piece,
+            "Kg" | "kg" | "KG" | "kilogram" | "kilograms" => Unit::Kilogram,
+            "g" | "gr" | "gram" | "grams" => Unit::Gram,
+            "lb" | "lbs" | "pound" | "pounds" => Unit::Pound,
+            "L" | "l" | "litre" | "litres" | "liter" | "liters" => Unit::Litre,
+            "ml" | "mL" | "millilitre" | "millilitres" | "milliliter" | "milliliters" => Unit::Millilitre,
+            _ => {
+                let known: Vec<&str> = Unit::ALL.iter().map(|unit| unit.symbol()).collect();
+                return Err(format!("unknown unit `{}`, expected one of: {}", s, known.join(", ")));
+            }
+        };
+        Ok(unit)
+    }
+}
+
+impl Serialize for Unit {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.symbol())
+    }
+}
+
+impl<'de> Deserialize<'de> for Unit {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
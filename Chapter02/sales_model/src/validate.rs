@@ -0,0 +1,225 @@
+use crate::{quantity, Decimal, Sale, SalesAndProducts, Timestamp, Unit};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueKind {
+    InvalidRecord,
+    DanglingProductId,
+    DuplicateProductId,
+    DuplicateSaleId,
+    NegativeQuantity,
+    UnknownUnit,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Issue {
+    pub kind: IssueKind,
+    /// Position of the record, e.g. `sales[2]`.
+    pub location: String,
+    pub id: String,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ValidationReport {
+    pub products: usize,
+    pub sales: usize,
+    pub issues: Vec<Issue>,
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    pub fn count(&self, kind: IssueKind) -> usize {
+        self.issues.iter().filter(|issue| issue.kind == kind).count()
+    }
+
+    fn push(&mut self, kind: IssueKind, location: String, id: String, message: String) {
+        self.issues.push(Issue {
+            kind,
+            location,
+            id,
+            message,
+        });
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for issue in self.issues.iter() {
+            writeln!(f, "{} ({}): {}", issue.location, issue.id, issue.message)?;
+        }
+        write!(
+            f,
+            "Checked {} products and {} sales: {} issues found",
+            self.products,
+            self.sales,
+            self.issues.len()
+        )
+    }
+}
+
+/// A sale with its unit as written in the file, so that an unknown unit is
+/// reported by [`validate_raw`] instead of failing the read.
+#[derive(Clone, Debug, Deserialize)]
+pub struct RawSale {
+    pub id: String,
+    pub product_id: i32,
+    pub date: Timestamp,
+    #[serde(with = "quantity")]
+    pub quantity: Decimal,
+    pub unit: String,
+}
+
+/// Products and sales read with [`read_any`](crate::read_any), keeping the
+/// sale units as written.
+pub type RawSalesAndProducts = SalesAndProducts<RawSale>;
+
+/// Checks every record and the references between them, collecting all the
+/// problems found instead of stopping at the first one.
+pub fn validate(data: &SalesAndProducts) -> ValidationReport {
+    check(data, &HashMap::new())
+}
+
+/// Like [`validate`], also reporting the sales whose unit is unknown.
+pub fn validate_raw(data: &RawSalesAndProducts) -> ValidationReport {
+    let mut unknown_units = HashMap::new();
+    let sales = data
+        .sales
+        .iter()
+        .enumerate()
+        .map(|(index, sale)| {
+            let unit = sale.unit.parse::<Unit>().unwrap_or_else(|e| {
+                unknown_units.insert(index, e);
+                Unit::default()
+            });
+            Sale::new(&sale.id, sale.product_id, sale.date, sale.quantity, unit)
+        })
+        .collect();
+    check(&SalesAndProducts::new(data.products.clone(), sales), &unknown_units)
+}
+
+// `unknown_units` holds the error for the unit of each sale, by index, that
+// could not be parsed
+fn check(data: &SalesAndProducts, unknown_units: &HashMap<usize, String>) -> ValidationReport {
+    let mut report = ValidationReport {
+        products: data.products.len(),
+        sales: data.sales.len(),
+        issues: vec![],
+    };
+
+    let mut product_ids: HashMap<i32, usize> = HashMap::new();
+    for (index, product) in data.products.iter().enumerate() {
+        let location = format!("products[{}]", index);
+        if let Err(e) = product.validate() {
+            report.push(IssueKind::InvalidRecord, location.clone(), product.id.to_string(), e);
+        }
+        match product_ids.entry(product.id) {
+            Entry::Occupied(first) => report.push(
+                IssueKind::DuplicateProductId,
+                location,
+                product.id.to_string(),
+                format!("product id {} already used by products[{}]", product.id, first.get()),
+            ),
+            Entry::Vacant(entry) => {
+                entry.insert(index);
+            }
+        }
+    }
+
+    let mut sale_ids: HashMap<&str, usize> = HashMap::new();
+    for (index, sale) in data.sales.iter().enumerate() {
+        let location = format!("sales[{}]", index);
+        if let Err(e) = sale.validate() {
+            report.push(IssueKind::InvalidRecord, location.clone(), sale.id.clone(), e);
+        }
+        match sale_ids.entry(&sale.id) {
+            Entry::Occupied(first) => report.push(
+                IssueKind::DuplicateSaleId,
+                location.clone(),
+                sale.id.clone(),
+                format!("sale id {} already used by sales[{}]", sale.id, first.get()),
+            ),
+            Entry::Vacant(entry) => {
+                entry.insert(index);
+            }
+        }
+        if !product_ids.contains_key(&sale.product_id) {
+            report.push(
+                IssueKind::DanglingProductId,
+                location.clone(),
+                sale.id.clone(),
+                format!("product_id {} does not refer to an existing product", sale.product_id),
+            );
+        }
+        if sale.quantity < Decimal::ZERO {
+            report.push(
+                IssueKind::NegativeQuantity,
+                location.clone(),
+                sale.id.clone(),
+                format!("quantity {} is negative", sale.quantity),
+            );
+        }
+        if let Some(e) = unknown_units.get(&index) {
+            report.push(IssueKind::UnknownUnit, location, sale.id.clone(), e.clone());
+        }
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{xml, Product};
+
+    fn raw_sale(id: &str, product_id: i32, unit: &str) -> RawSale {
+        RawSale {
+            id: id.to_string(),
+            product_id,
+            date: Timestamp::default(),
+            quantity: Decimal::ONE,
+            unit: unit.to_string(),
+        }
+    }
+
+    #[test]
+    fn unknown_units_are_reported_with_the_other_issues() {
+        let data = RawSalesAndProducts {
+            products: vec![Product::new(1, "fruit", "apple")],
+            sales: vec![raw_sale("a", 1, "kg"), raw_sale("b", 2, "oz"), raw_sale("c", 1, "")],
+        };
+        let report = validate_raw(&data);
+
+        let issues: Vec<(IssueKind, &str)> = report
+            .issues
+            .iter()
+            .map(|issue| (issue.kind, issue.location.as_str()))
+            .collect();
+        assert_eq!(
+            issues,
+            [
+                (IssueKind::DanglingProductId, "sales[1]"),
+                (IssueKind::UnknownUnit, "sales[1]"),
+                (IssueKind::UnknownUnit, "sales[2]"),
+            ]
+        );
+        assert!(report.issues[1].message.starts_with("unknown unit `oz`"));
+    }
+
+    #[test]
+    fn xml_units_are_read_as_written() {
+        let content = "<sales-and-products><sale><id>a</id><product-id>1</product-id>\
+                       <date>0</date><quantity>1</quantity><unit>oz</unit></sale></sales-and-products>";
+        let (products, sales): (Vec<Product>, Vec<RawSale>) = xml::read_document(content.as_bytes()).unwrap();
+        assert!(products.is_empty());
+        assert_eq!(sales[0].unit, "oz");
+        assert!(xml::from_str(content).is_err());
+    }
+}
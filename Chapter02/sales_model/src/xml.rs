@@ -0,0 +1,849 @@
+use crate::stream::Record;
+use crate::{Product, Sale, SalesAndProducts, TimestampFormat, WithFormat};
+use serde::de::{self, DeserializeOwned, DeserializeSeed, IntoDeserializer, MapAccess, SeqAccess, Visitor};
+use serde::ser::{self, Impossible, SerializeSeq, SerializeStruct};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use xml::common::{Position, TextPosition};
+use xml::reader::{EventReader, XmlEvent};
+use xml::writer::{self as xml_writer, EmitterConfig, EventWriter};
+
+pub const ROOT_ELEMENT: &str = "sales-and-products";
+
+/// Layout of `sales.xml`: a root element holding repeated `<product>` and
+/// `<sale>` elements.
+#[derive(Deserialize)]
+struct Document<S> {
+    #[serde(rename = "product", default)]
+    products: Vec<Product>,
+    // A plain `default` would require `S: Default`
+    #[serde(rename = "sale", default = "Vec::new")]
+    sales: Vec<S>,
+}
+
+#[derive(Serialize)]
+struct DocumentRef<'a> {
+    #[serde(rename = "product")]
+    products: &'a [Product],
+    #[serde(rename = "sale")]
+    sales: WithFormat<'a, [Sale]>,
+}
+
+#[derive(Debug)]
+pub struct Error {
+    message: String,
+    position: Option<TextPosition>,
+}
+
+impl Error {
+    fn new(message: String, position: TextPosition) -> Self {
+        Error {
+            message,
+            position: Some(position),
+        }
+    }
+
+    fn or_at(mut self, position: TextPosition) -> Self {
+        if self.position.is_none() {
+            self.position = Some(position);
+        }
+        self
+    }
+
+    /// Line and column (both starting at 1) where the error was found.
+    pub fn location(&self) -> Option<(u64, u64)> {
+        self.position.map(|p| (p.row + 1, p.column + 1))
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.location() {
+            Some((line, column)) => write!(f, "{} at line {}, column {}", self.message, line, column),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error {
+            message: msg.to_string(),
+            position: None,
+        }
+    }
+}
+
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        de::Error::custom(msg)
+    }
+}
+
+impl From<xml_writer::Error> for Error {
+    fn from(e: xml_writer::Error) -> Self {
+        de::Error::custom(e)
+    }
+}
+
+impl From<xml::reader::Error> for Error {
+    fn from(e: xml::reader::Error) -> Self {
+        Error::new(e.msg().to_string(), e.position())
+    }
+}
+
+/// An XML element with its text content and children, keeping the position
+/// of the start tag so errors can point back into the document.
+#[derive(Debug)]
+pub struct Element {
+    pub name: String,
+    pub text: String,
+    pub children: Vec<Element>,
+    pub position: TextPosition,
+}
+
+impl Element {
+    /// Reads the rest of an element whose start tag has just been consumed.
+    pub fn read<R: Read>(
+        parser: &mut EventReader<R>,
+        name: String,
+        position: TextPosition,
+    ) -> Result<Element, Error> {
+        let mut element = Element {
+            name,
+            text: String::new(),
+            children: vec![],
+            position,
+        };
+
+        loop {
+            let event = parser.next()?;
+            let position = parser.position();
+            match event {
+                XmlEvent::StartElement { name, .. } => {
+                    let child = Element::read(parser, name.local_name, position)?;
+                    element.children.push(child);
+                }
+                XmlEvent::Characters(text) | XmlEvent::CData(text) => {
+                    element.text.push_str(&text);
+                }
+                XmlEvent::EndElement { .. } => return Ok(element),
+                XmlEvent::EndDocument => {
+                    return Err(Error::new(
+                        format!("unexpected end of document inside <{}>", element.name),
+                        position,
+                    ))
+                }
+                _ => {}
+            }
+        }
+    }
+
+    pub fn deserialize<T: DeserializeOwned>(&self) -> Result<T, Error> {
+        T::deserialize(ElementDeserializer(self))
+    }
+
+    /// Groups children by element name, in order of first appearance.
+    /// Kebab-case names are mapped onto snake_case field names.
+    fn grouped_children(&self) -> Vec<(String, Vec<&Element>)> {
+        let mut groups: Vec<(String, Vec<&Element>)> = vec![];
+        for child in self.children.iter() {
+            let key = child.name.replace('-', "_");
+            match groups.iter_mut().find(|(name, _)| *name == key) {
+                Some((_, elements)) => elements.push(child),
+                None => groups.push((key, vec![child])),
+            }
+        }
+        groups
+    }
+
+    fn parse<T>(&self, kind: &str) -> Result<T, Error>
+    where
+        T: std::str::FromStr,
+        T::Err: fmt::Display,
+    {
+        let text = self.text.trim();
+        text.parse().map_err(|e| {
+            Error::new(
+                format!("invalid {} `{}` in <{}>: {}", kind, text, self.name, e),
+                self.position,
+            )
+        })
+    }
+}
+
+pub fn read_xml<S: DeserializeOwned, P: AsRef<Path>>(input_path: P) -> Result<SalesAndProducts<S>, String> {
+    let file = File::open(input_path).map_err(|e| e.to_string())?;
+    read_document(BufReader::new(file)).map(SalesAndProducts::from).map_err(|e| e.to_string())
+}
+
+pub fn from_reader<R: Read>(reader: R) -> Result<SalesAndProducts, Error> {
+    read_document(reader).map(SalesAndProducts::from)
+}
+
+/// Reads the products and the sales of a document, with sales of any type
+/// laid out like [`Sale`].
+pub(crate) fn read_document<S: DeserializeOwned, R: Read>(reader: R) -> Result<(Vec<Product>, Vec<S>), Error> {
+    let mut parser = EventReader::new(reader);
+    loop {
+        let event = parser.next()?;
+        let position = parser.position();
+        match event {
+            XmlEvent::StartElement { name, .. } => {
+                if name.local_name != ROOT_ELEMENT {
+                    return Err(Error::new(
+                        format!("expected <{}>, found <{}>", ROOT_ELEMENT, name.local_name),
+                        position,
+                    ));
+                }
+                let root = Element::read(&mut parser, name.local_name, position)?;
+                let document: Document<S> = root.deserialize()?;
+                return Ok((document.products, document.sales));
+            }
+            XmlEvent::EndDocument => {
+                return Err(Error::new(format!("missing <{}>", ROOT_ELEMENT), position))
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Reads the `<product>` and `<sale>` elements of a sales XML document one at
+/// a time, in document order, holding only the element being read in
+/// memory. Other elements under the root are skipped, and the iteration
+/// stops at the first error.
+pub struct XmlRecords<R: Read> {
+    parser: EventReader<R>,
+    started: bool,
+    done: bool,
+}
+
+pub fn xml_records<P: AsRef<Path>>(input_path: P) -> Result<XmlRecords<BufReader<File>>, String> {
+    let path = input_path.as_ref();
+    let file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(XmlRecords::new(BufReader::new(file)))
+}
+
+impl<R: Read> XmlRecords<R> {
+    pub fn new(reader: R) -> Self {
+        XmlRecords {
+            parser: EventReader::new(reader),
+            started: false,
+            done: false,
+        }
+    }
+
+    fn next_record(&mut self) -> Result<Option<Record>, Error> {
+        loop {
+            let event = self.parser.next()?;
+            let position = self.parser.position();
+            match event {
+                XmlEvent::StartElement { name, .. } if !self.started => {
+                    if name.local_name != ROOT_ELEMENT {
+                        return Err(Error::new(
+                            format!("expected <{}>, found <{}>", ROOT_ELEMENT, name.local_name),
+                            position,
+                        ));
+                    }
+                    self.started = true;
+                }
+                XmlEvent::StartElement { name, .. } => {
+                    let element = Element::read(&mut self.parser, name.local_name, position)?;
+                    match element.name.as_str() {
+                        "product" => return element.deserialize().map(|product| Some(Record::Product(product))),
+                        "sale" => return element.deserialize().map(|sale| Some(Record::Sale(sale))),
+                        _ => {}
+                    }
+                }
+                XmlEvent::EndElement { .. } => {
+                    self.done = true;
+                    return Ok(None);
+                }
+                XmlEvent::EndDocument => {
+                    return Err(Error::new(format!("missing <{}>", ROOT_ELEMENT), position))
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl<R: Read> Iterator for XmlRecords<R> {
+    type Item = Result<Record, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let record = self.next_record();
+        if !matches!(record, Ok(Some(_))) {
+            self.done = true;
+        }
+        record.transpose()
+    }
+}
+
+pub fn from_str(content: &str) -> Result<SalesAndProducts, Error> {
+    from_reader(content.as_bytes())
+}
+
+pub fn write_xml<P: AsRef<Path>>(
+    output_path: P,
+    data: &SalesAndProducts,
+    date_format: &TimestampFormat,
+) -> Result<(), String> {
+    let file = File::create(output_path).map_err(|e| e.to_string())?;
+    to_writer(BufWriter::new(file), data, date_format).map_err(|e| e.to_string())
+}
+
+pub fn to_writer<W: Write>(writer: W, data: &SalesAndProducts, date_format: &TimestampFormat) -> Result<(), Error> {
+    let mut writer = EmitterConfig::new()
+        .perform_indent(true)
+        .indent_string("    ")
+        .create_writer(writer);
+    writer.write(xml_writer::XmlEvent::StartDocument {
+        version: xml::common::XmlVersion::Version10,
+        encoding: Some("utf-8"),
+        standalone: None,
+    })?;
+
+    let document = DocumentRef {
+        products: &data.products,
+        sales: WithFormat::new(&data.sales, date_format),
+    };
+    document.serialize(ElementSerializer::new(&mut writer, ROOT_ELEMENT))?;
+    writer.inner_mut().write_all(b"\n").map_err(de::Error::custom)
+}
+
+pub fn to_string(data: &SalesAndProducts, date_format: &TimestampFormat) -> Result<String, Error> {
+    let mut buffer = vec![];
+    to_writer(&mut buffer, data, date_format)?;
+    String::from_utf8(buffer).map_err(de::Error::custom)
+}
+
+struct ElementDeserializer<'a>(&'a Element);
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident, $kind:expr;)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                visitor.$visit(self.0.parse($kind)?)
+            }
+        )*
+    };
+}
+
+impl<'de, 'a> de::Deserializer<'de> for ElementDeserializer<'a> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.0.children.is_empty() {
+            visitor.visit_str(&self.0.text)
+        } else {
+            self.deserialize_map(visitor)
+        }
+    }
+
+    deserialize_parsed! {
+        deserialize_bool => visit_bool, "boolean";
+        deserialize_i8 => visit_i8, "integer";
+        deserialize_i16 => visit_i16, "integer";
+        deserialize_i32 => visit_i32, "integer";
+        deserialize_i64 => visit_i64, "integer";
+        deserialize_u8 => visit_u8, "integer";
+        deserialize_u16 => visit_u16, "integer";
+        deserialize_u32 => visit_u32, "integer";
+        deserialize_u64 => visit_u64, "integer";
+        deserialize_f32 => visit_f32, "number";
+        deserialize_f64 => visit_f64, "number";
+        deserialize_char => visit_char, "character";
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_str(&self.0.text)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_str(&self.0.text)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let position = self.0.position;
+        visitor
+            .visit_enum(self.0.text.trim().into_deserializer())
+            .map_err(|e: Error| e.or_at(position))
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let position = self.0.position;
+        let groups = self.0.grouped_children();
+        visitor
+            .visit_map(ChildrenAccess {
+                groups: groups.into_iter(),
+                value: None,
+            })
+            .map_err(|e| e.or_at(position))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    serde::forward_to_deserialize_any! {
+        bytes byte_buf unit unit_struct seq tuple tuple_struct identifier
+    }
+}
+
+struct ChildrenAccess<'a> {
+    groups: std::vec::IntoIter<(String, Vec<&'a Element>)>,
+    value: Option<Vec<&'a Element>>,
+}
+
+impl<'de, 'a> MapAccess<'de> for ChildrenAccess<'a> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, Error> {
+        match self.groups.next() {
+            Some((key, elements)) => {
+                self.value = Some(elements);
+                seed.deserialize(key.into_deserializer()).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let elements = self.value.take().expect("value requested before key");
+        seed.deserialize(GroupDeserializer(elements))
+    }
+}
+
+/// All the children sharing one element name: a sequence when the field is a
+/// `Vec`, otherwise the first element.
+struct GroupDeserializer<'a>(Vec<&'a Element>);
+
+macro_rules! forward_to_first {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                ElementDeserializer(self.0[0]).$method(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de, 'a> de::Deserializer<'de> for GroupDeserializer<'a> {
+    type Error = Error;
+
+    forward_to_first! {
+        deserialize_any deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32
+        deserialize_i64 deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64
+        deserialize_f32 deserialize_f64 deserialize_char deserialize_str deserialize_string
+        deserialize_option deserialize_map deserialize_ignored_any
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_seq(ElementsAccess(self.0.into_iter()))
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        ElementDeserializer(self.0[0]).deserialize_newtype_struct(name, visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        ElementDeserializer(self.0[0]).deserialize_enum(name, variants, visitor)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        ElementDeserializer(self.0[0]).deserialize_struct(name, fields, visitor)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bytes byte_buf unit unit_struct tuple tuple_struct identifier
+    }
+}
+
+struct ElementsAccess<'a>(std::vec::IntoIter<&'a Element>);
+
+impl<'de, 'a> SeqAccess<'de> for ElementsAccess<'a> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, Error> {
+        match self.0.next() {
+            Some(element) => seed.deserialize(ElementDeserializer(element)).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Writes a value as an element named after the field holding it: structs
+/// become child elements (snake_case field names written as kebab-case),
+/// sequences repeat the element and scalars become its text.
+struct ElementSerializer<'a, W: Write> {
+    writer: &'a mut EventWriter<W>,
+    name: String,
+}
+
+impl<'a, W: Write> ElementSerializer<'a, W> {
+    fn new(writer: &'a mut EventWriter<W>, name: &str) -> Self {
+        ElementSerializer {
+            writer,
+            name: name.replace('_', "-"),
+        }
+    }
+
+    fn write_text(self, text: &str) -> Result<(), Error> {
+        self.writer
+            .write(xml_writer::XmlEvent::start_element(self.name.as_str()))?;
+        if !text.is_empty() {
+            self.writer.write(xml_writer::XmlEvent::characters(text))?;
+        }
+        self.writer.write(xml_writer::XmlEvent::end_element())?;
+        Ok(())
+    }
+
+    fn unsupported(&self, kind: &str) -> Error {
+        de::Error::custom(format!("cannot write {} as XML element <{}>", kind, self.name))
+    }
+}
+
+macro_rules! serialize_display {
+    ($($method:ident: $ty:ty)*) => {
+        $(
+            fn $method(self, v: $ty) -> Result<(), Error> {
+                self.write_text(&v.to_string())
+            }
+        )*
+    };
+}
+
+impl<'a, W: Write> ser::Serializer for ElementSerializer<'a, W> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Self;
+    type SerializeTuple = Impossible<(), Error>;
+    type SerializeTupleStruct = Impossible<(), Error>;
+    type SerializeTupleVariant = Impossible<(), Error>;
+    type SerializeMap = Impossible<(), Error>;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Impossible<(), Error>;
+
+    serialize_display! {
+        serialize_bool: bool
+        serialize_i8: i8
+        serialize_i16: i16
+        serialize_i32: i32
+        serialize_i64: i64
+        serialize_u8: u8
+        serialize_u16: u16
+        serialize_u32: u32
+        serialize_u64: u64
+        serialize_f32: f32
+        serialize_f64: f64
+        serialize_char: char
+        serialize_str: &str
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<(), Error> {
+        Err(self.unsupported("bytes"))
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        self.write_text("")
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        self.write_text("")
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<(), Error> {
+        self.write_text(variant)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<(), Error> {
+        Err(self.unsupported("an enum variant"))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self, Error> {
+        Ok(self)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Error> {
+        Err(self.unsupported("a tuple"))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        Err(self.unsupported("a tuple struct"))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(self.unsupported("an enum variant"))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Err(self.unsupported("a map"))
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self, Error> {
+        self.writer
+            .write(xml_writer::XmlEvent::start_element(self.name.as_str()))?;
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Err(self.unsupported("an enum variant"))
+    }
+}
+
+impl<'a, W: Write> SerializeSeq for ElementSerializer<'a, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        value.serialize(ElementSerializer::new(self.writer, &self.name))
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<'a, W: Write> SerializeStruct for ElementSerializer<'a, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, key: &'static str, value: &T) -> Result<(), Error> {
+        value.serialize(ElementSerializer::new(self.writer, key))
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.writer.write(xml_writer::XmlEvent::end_element())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Decimal, Timestamp, Unit};
+
+    fn date(epoch: i64) -> Timestamp {
+        Timestamp::from_epoch(epoch).unwrap()
+    }
+
+    fn data() -> SalesAndProducts {
+        SalesAndProducts::new(
+            vec![Product::new(862, "fruit", "cherry"), Product::new(236, "furniture", "table & <chair>")],
+            vec![
+                Sale::new("2020-3987", 862, date(1238563890), Decimal::new(753, 3), Unit::Kilogram),
+                Sale::new("2020-\"3992\"", 236, date(1238567890), Decimal::ONE, Unit::Piece),
+            ],
+        )
+    }
+
+    fn error_at(content: &str) -> (String, Option<(u64, u64)>) {
+        let e = from_str(content).unwrap_err();
+        (e.message.clone(), e.location())
+    }
+
+    #[test]
+    fn written_documents_read_back() {
+        for format in [TimestampFormat::Epoch, TimestampFormat::Rfc3339] {
+            let content = to_string(&data(), &format).unwrap();
+            assert_eq!(from_str(&content).unwrap(), data());
+        }
+    }
+
+    #[test]
+    fn elements_are_kebab_case_and_repeated() {
+        let content = to_string(&data(), &TimestampFormat::Epoch).unwrap();
+        assert!(content.starts_with("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<sales-and-products>"));
+        assert_eq!(content.matches("<product>").count(), 2);
+        assert_eq!(content.matches("<sale>").count(), 2);
+        assert!(content.contains("<product-id>862</product-id>"));
+        assert!(content.contains("<date>1238563890</date>"));
+        assert!(content.contains("<unit>Kg</unit>"));
+        assert!(content.ends_with("</sales-and-products>\n"));
+    }
+
+    #[test]
+    fn special_characters_are_escaped() {
+        let content = to_string(&data(), &TimestampFormat::Epoch).unwrap();
+        assert!(content.contains("<name>table &amp; &lt;chair&gt;</name>"));
+        assert!(content.contains("<id>2020-\"3992\"</id>"));
+
+        let content = "<sales-and-products><product><id>1</id><category>a&lt;b</category>\
+                       <name><![CDATA[x & y]]></name></product></sales-and-products>";
+        let data = from_str(content).unwrap();
+        assert_eq!(data.products, [Product::new(1, "a<b", "x & y")]);
+    }
+
+    #[test]
+    fn products_and_sales_may_be_interleaved_or_missing() {
+        let sale = "<sale><id>a</id><product-id>1</product-id><date>0</date><quantity>2</quantity><unit>g</unit></sale>";
+        let product = |id: i32| format!("<product><id>{}</id><category>c</category><name>n</name></product>", id);
+        let content = format!(
+            "<sales-and-products>{}{}<note>skipped</note>{}{}</sales-and-products>",
+            product(1),
+            sale,
+            product(2),
+            sale
+        );
+        let data = from_str(&content).unwrap();
+        assert_eq!(data.products.iter().map(|p| p.id).collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(data.sales.len(), 2);
+        assert_eq!(data.sales[0].unit, Unit::Gram);
+
+        let records: Vec<Record> = XmlRecords::new(content.as_bytes()).collect::<Result<_, _>>().unwrap();
+        let kinds: Vec<&str> = records
+            .iter()
+            .map(|record| match record {
+                Record::Product(_) => "product",
+                Record::Sale(_) => "sale",
+            })
+            .collect();
+        assert_eq!(kinds, ["product", "sale", "product", "sale"]);
+
+        assert_eq!(from_str("<sales-and-products/>").unwrap(), SalesAndProducts::default());
+        assert_eq!(from_str(&format!("<sales-and-products>{}</sales-and-products>", product(3))).unwrap().sales, []);
+    }
+
+    #[test]
+    fn malformed_documents_give_the_line_and_column() {
+        let (message, location) = error_at("<sales-and-products>\n  <product>\n    <id>1</id>\n</sales-and-products>");
+        assert_eq!(message, "Unexpected closing tag: sales-and-products != product");
+        assert_eq!(location, Some((4, 21)));
+
+        let (message, location) = error_at("<sales-and-products>\n  <product>");
+        assert_eq!(message, "Unexpected end of stream: still inside the root element");
+        assert_eq!(location, Some((2, 12)));
+
+        let (message, location) = error_at("<?xml version=\"1.0\"?>\n<sales>\n</sales>");
+        assert_eq!(message, "expected <sales-and-products>, found <sales>");
+        assert_eq!(location, Some((2, 1)));
+        let (message, _) = error_at("<?xml version=\"1.0\"?>\n<!-- nothing -->");
+        assert_eq!(message, "Unexpected end of stream: no root element found");
+    }
+
+    #[test]
+    fn invalid_values_give_the_position_of_their_element() {
+        let content = "<sales-and-products>\n\
+                       <product>\n\
+                       <id>1</id><category>c</category><name>n</name>\n\
+                       </product>\n\
+                       <sale>\n\
+                       <id>a</id>\n\
+                       <product-id>one</product-id>\n\
+                       </sale>\n\
+                       </sales-and-products>";
+        let (message, location) = error_at(content);
+        assert_eq!(message, "invalid integer `one` in <product-id>: invalid digit found in string");
+        assert_eq!(location, Some((7, 1)));
+
+        let content = "<sales-and-products>\n  <sale><id>a</id><product-id>1</product-id><date>0</date>\
+                       <quantity>1</quantity><unit>oz</unit></sale>\n</sales-and-products>";
+        let (message, location) = error_at(content);
+        assert!(message.contains("oz"), "{}", message);
+        assert_eq!(location, Some((2, 3)));
+
+        let (message, location) = error_at("<sales-and-products>\n  <product><id>1</id></product>\n</sales-and-products>");
+        assert_eq!(message, "missing field `category`");
+        assert_eq!(location, Some((2, 3)));
+    }
+
+    #[test]
+    fn records_stop_at_the_first_error() {
+        let content = "<sales-and-products><product><id>x</id></product><product><id>2</id></product></sales-and-products>";
+        let mut records = XmlRecords::new(content.as_bytes());
+        assert!(records.next().unwrap().is_err());
+        assert!(records.next().is_none());
+    }
+}
@@ -1,49 +1,102 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use sales_model::config::config_file;
 use std::fs::File;
 use std::io::Read;
-use toml;
-use toml::Table;
+use toml_edit::{Document, Item};
 
+mod path;
+
+use path::Path;
+
+/// Queries and edits any TOML file by dotted path, e.g. `servers[0].host`.
+/// Edits keep the comments and key order of the file.
 #[derive(Parser, Debug)]
 struct Args {
-    #[arg(long)]
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// TOML file, or a directory holding config.toml
+    #[arg(long, global = true)]
     config_path: Option<std::path::PathBuf>,
 }
 
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print the value at a path in TOML syntax
+    Get { path: Path },
+    /// Set a value, e.g. `set redis.host=cache.local`
+    Set {
+        #[arg(value_name = "PATH=VALUE")]
+        assignment: String,
+    },
+    /// Remove a value or table
+    Delete { path: Path },
+    /// Print every value, or every value under a path
+    List { path: Option<Path> },
+}
+
 fn main() {
     let args = Args::parse();
-    let content: Table;
 
     let config_path = match args.config_path {
-        Some(config_path) => config_path,
+        Some(config_path) => config_file(config_path),
         None => {
             println!("No config path provided. Use --config-path to specify a config");
             return;
         }
     };
 
+    if let Err(e) = run(args.command, &config_path) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
 
-    content = read_toml_dynamically(config_path).unwrap();
-    println!("{:?}", content);
+fn run(command: Option<Command>, file_path: &std::path::Path) -> Result<(), String> {
+    let mut content: Document = read_toml_dynamically(file_path)?;
 
-    println!(
-        "[Postgresql].Database: {}",
-        content
-            .get("postgresql")
-            .unwrap()
-            .get("database")
-            .unwrap()
-            .as_str()
-            .unwrap()
-    );
+    match command.unwrap_or(Command::List { path: None }) {
+        Command::Get { path } => {
+            let item = path::get(content.as_item(), &path).ok_or_else(|| format!("`{}` not found", path))?;
+            match item {
+                Item::Value(value) if !value.is_inline_table() => println!("{}", path::literal(value)),
+                _ => print_values(item, &path),
+            }
+        }
+        Command::Set { assignment } => {
+            let (path, value) = assignment
+                .split_once('=')
+                .ok_or_else(|| format!("invalid assignment `{}`, expected PATH=VALUE", assignment))?;
+            let path: Path = path.trim().parse()?;
+            path::set(content.as_item_mut(), &path, value.trim())?;
+            write_toml(file_path, &content)?;
+        }
+        Command::Delete { path } => {
+            path::delete(content.as_item_mut(), &path)?;
+            write_toml(file_path, &content)?;
+        }
+        Command::List { path } => {
+            let path = path.unwrap_or_else(Path::root);
+            let item = path::get(content.as_item(), &path).ok_or_else(|| format!("`{}` not found", path))?;
+            print_values(item, &path);
+        }
+    }
+    Ok(())
 }
 
-fn read_toml_dynamically(config_path: std::path::PathBuf) -> Result<Table, String> {
-    let file_path = config_path.join("config.toml");
+fn print_values(item: &Item, path: &Path) {
+    for (path, value) in path::list(item, path) {
+        println!("{} = {}", path, path::literal(&value));
+    }
+}
 
+fn read_toml_dynamically(file_path: &std::path::Path) -> Result<Document, String> {
     let mut file = File::open(file_path).map_err(|e| e.to_string())?;
     let mut contents = String::new();
     file.read_to_string(&mut contents).map_err(|e| e.to_string())?;
-    contents.parse::<Table>().map_err(|e| e.to_string())
+    contents.parse::<Document>().map_err(|e| e.to_string())
 }
 
+fn write_toml(file_path: &std::path::Path, content: &Document) -> Result<(), String> {
+    std::fs::write(file_path, content.to_string()).map_err(|e| e.to_string())
+}
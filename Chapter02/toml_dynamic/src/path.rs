@@ -0,0 +1,425 @@
+use std::fmt;
+use std::str::FromStr;
+use toml_edit::{Item, Table, Value};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// A dotted path into a TOML document, as in `servers[0].host`. Keys
+/// holding dots can be quoted: `"a.b".c`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Path(Vec<Segment>);
+
+impl Path {
+    pub fn root() -> Self {
+        Path(vec![])
+    }
+
+    fn child(&self, segment: Segment) -> Path {
+        let mut segments = self.0.clone();
+        segments.push(segment);
+        Path(segments)
+    }
+}
+
+impl FromStr for Path {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: &str| format!("invalid path `{}`: {}", s, reason);
+        let mut segments = vec![];
+        let mut chars = s.chars().peekable();
+
+        loop {
+            let mut key = String::new();
+            if chars.peek() == Some(&'"') {
+                chars.next();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) => key.push(c),
+                        None => return Err(invalid("unterminated quote")),
+                    }
+                }
+            } else {
+                while let Some(&c) = chars.peek() {
+                    if c == '.' || c == '[' {
+                        break;
+                    }
+                    key.push(c);
+                    chars.next();
+                }
+                if key.is_empty() {
+                    return Err(invalid("empty key"));
+                }
+            }
+            segments.push(Segment::Key(key));
+
+            while chars.peek() == Some(&'[') {
+                chars.next();
+                let mut index = String::new();
+                loop {
+                    match chars.next() {
+                        Some(']') => break,
+                        Some(c) => index.push(c),
+                        None => return Err(invalid("missing `]`")),
+                    }
+                }
+                let index = index.trim().parse().map_err(|_| invalid("index is not a number"))?;
+                segments.push(Segment::Index(index));
+            }
+
+            match chars.next() {
+                None => break,
+                Some('.') => continue,
+                Some(c) => return Err(invalid(&format!("unexpected `{}`", c))),
+            }
+        }
+
+        Ok(Path(segments))
+    }
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, segment) in self.0.iter().enumerate() {
+            match segment {
+                Segment::Key(key) => {
+                    if i > 0 {
+                        f.write_str(".")?;
+                    }
+                    if key.is_empty() || key.contains(['.', '[', ']', '"']) {
+                        write!(f, "\"{}\"", key)?;
+                    } else {
+                        f.write_str(key)?;
+                    }
+                }
+                Segment::Index(index) => write!(f, "[{}]", index)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn get<'a>(root: &'a Item, path: &Path) -> Option<&'a Item> {
+    let mut item = root;
+    for segment in path.0.iter() {
+        item = match segment {
+            Segment::Key(key) => item.as_table_like()?.get(key)?,
+            Segment::Index(index) => item.get(*index)?,
+        };
+    }
+    Some(item)
+}
+
+// `Item::get_mut` inserts missing keys, so keys go through the table API
+fn get_mut<'a>(root: &'a mut Item, segments: &[Segment]) -> Option<&'a mut Item> {
+    let mut item = root;
+    for segment in segments.iter() {
+        item = match segment {
+            Segment::Key(key) => item.as_table_like_mut()?.get_mut(key)?,
+            Segment::Index(index) => item.get_mut(*index)?,
+        };
+    }
+    Some(item)
+}
+
+/// Sets the value at `path` from its TOML form, creating missing tables on
+/// the way. Values replacing a string stay strings, otherwise the text is
+/// parsed as a TOML value and falls back to a string, so `port=5433` sets
+/// an integer and `host=cache.local` a string.
+pub fn set(root: &mut Item, path: &Path, raw: &str) -> Result<(), String> {
+    let (last, parents) = path.0.split_last().ok_or("cannot set the whole document")?;
+
+    let mut parent = root;
+    for (i, segment) in parents.iter().enumerate() {
+        let at = Path(path.0[..=i].to_vec());
+        parent = match segment {
+            Segment::Key(key) => {
+                let table = parent
+                    .as_table_like_mut()
+                    .ok_or_else(|| format!("`{}` is not a table", Path(path.0[..i].to_vec())))?;
+                if table.get(key).is_none() {
+                    // Implicit, so only the innermost table gets a header
+                    let mut child = Table::new();
+                    child.set_implicit(true);
+                    table.insert(key, Item::Table(child));
+                }
+                table.get_mut(key).ok_or_else(|| format!("`{}` not found", at))?
+            }
+            Segment::Index(index) => parent.get_mut(*index).ok_or_else(|| format!("`{}` not found", at))?,
+        };
+    }
+
+    match last {
+        Segment::Key(key) => {
+            let table = parent
+                .as_table_like_mut()
+                .ok_or_else(|| format!("`{}` is not a table", Path(parents.to_vec())))?;
+            match table.get_mut(key) {
+                Some(Item::Value(old)) => *old = replace(old, raw),
+                Some(Item::None) | None => {
+                    table.insert(key, Item::Value(parse_value(raw)));
+                }
+                Some(_) => return Err(format!("`{}` is a table, set its keys instead", path)),
+            }
+        }
+        Segment::Index(index) => {
+            let array = parent
+                .as_array_mut()
+                .ok_or_else(|| format!("`{}` is not an array of values", Path(parents.to_vec())))?;
+            let old = array.get_mut(*index).ok_or_else(|| format!("`{}` not found", path))?;
+            *old = replace(old, raw);
+        }
+    }
+    Ok(())
+}
+
+/// Removes the value or table at `path`.
+pub fn delete(root: &mut Item, path: &Path) -> Result<(), String> {
+    let (last, parents) = path.0.split_last().ok_or("cannot delete the whole document")?;
+    let not_found = || format!("`{}` not found", path);
+    let parent = get_mut(root, parents).ok_or_else(not_found)?;
+
+    match last {
+        Segment::Key(key) => {
+            parent
+                .as_table_like_mut()
+                .and_then(|table| table.remove(key))
+                .ok_or_else(not_found)?;
+        }
+        Segment::Index(index) => match parent {
+            Item::ArrayOfTables(tables) if *index < tables.len() => tables.remove(*index),
+            Item::Value(Value::Array(array)) if *index < array.len() => {
+                array.remove(*index);
+            }
+            _ => return Err(not_found()),
+        },
+    }
+    Ok(())
+}
+
+/// Every value under `item` with its full path. Arrays of values are listed
+/// whole, arrays of tables element by element.
+pub fn list(item: &Item, path: &Path) -> Vec<(Path, Value)> {
+    let mut out = vec![];
+    collect(item, path, &mut out);
+    out
+}
+
+fn collect(item: &Item, path: &Path, out: &mut Vec<(Path, Value)>) {
+    match item {
+        Item::None => {}
+        Item::Value(Value::InlineTable(_)) | Item::Table(_) => {
+            if let Some(table) = item.as_table_like() {
+                for (key, child) in table.iter() {
+                    collect(child, &path.child(Segment::Key(key.to_string())), out);
+                }
+            }
+        }
+        Item::ArrayOfTables(tables) => {
+            for (i, table) in tables.iter().enumerate() {
+                let child = Item::Table(table.clone());
+                collect(&child, &path.child(Segment::Index(i)), out);
+            }
+        }
+        Item::Value(value) => out.push((path.clone(), value.clone())),
+    }
+}
+
+/// The value in TOML syntax, without the surrounding whitespace and comments.
+pub fn literal(value: &Value) -> String {
+    value.clone().decorated("", "").to_string()
+}
+
+// Keeps the comments around the replaced value
+fn replace(old: &Value, raw: &str) -> Value {
+    let mut value = match old {
+        Value::String(_) => Value::from(raw),
+        _ => parse_value(raw),
+    };
+    *value.decor_mut() = old.decor().clone();
+    value
+}
+
+fn parse_value(raw: &str) -> Value {
+    raw.parse::<Value>().unwrap_or_else(|_| Value::from(raw))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use toml_edit::Document;
+
+    const CONFIG: &str = r#"# Connection settings
+[postgresql]
+host = "localhost" # the server
+port = 5432
+
+[[servers]]
+name = "alpha"
+tags = ["a", "b"]
+
+[[servers]]
+name = "beta"
+"#;
+
+    fn document() -> Document {
+        CONFIG.parse().unwrap()
+    }
+
+    fn path(s: &str) -> Path {
+        s.parse().unwrap()
+    }
+
+    fn key(key: &str) -> Segment {
+        Segment::Key(key.to_string())
+    }
+
+    fn get_literal(document: &Document, s: &str) -> Option<String> {
+        get(document.as_item(), &path(s)).and_then(|item| item.as_value()).map(literal)
+    }
+
+    #[test]
+    fn paths_are_split_into_keys_and_indices() {
+        assert_eq!(path("port"), Path(vec![key("port")]));
+        assert_eq!(path("postgresql.port"), Path(vec![key("postgresql"), key("port")]));
+        assert_eq!(
+            path("servers[1].tags[ 0 ]"),
+            Path(vec![key("servers"), Segment::Index(1), key("tags"), Segment::Index(0)])
+        );
+        assert_eq!(path("matrix[0][2]"), Path(vec![key("matrix"), Segment::Index(0), Segment::Index(2)]));
+        assert_eq!(path(r#""a.b".c"#), Path(vec![key("a.b"), key("c")]));
+        assert_eq!(path(r#"a."[x]""#), Path(vec![key("a"), key("[x]")]));
+        assert_eq!(path(r#""""#), Path(vec![key("")]));
+    }
+
+    #[test]
+    fn paths_are_displayed_as_parsed() {
+        for s in ["postgresql.port", "servers[1].tags[0]", r#""a.b".c"#, r#"a."[x]""#, r#""".a"#] {
+            assert_eq!(path(s).to_string(), s);
+        }
+        assert_eq!(Path::root().to_string(), "");
+    }
+
+    #[test]
+    fn malformed_paths_are_rejected() {
+        let error = |s: &str| s.parse::<Path>().unwrap_err();
+        assert_eq!(error("a..b"), "invalid path `a..b`: empty key");
+        assert_eq!(error(""), "invalid path ``: empty key");
+        assert_eq!(error("a."), "invalid path `a.`: empty key");
+        assert_eq!(error("[0]"), "invalid path `[0]`: empty key");
+        assert_eq!(error(r#""a.b"#), r#"invalid path `"a.b`: unterminated quote"#);
+        assert_eq!(error("a[0"), "invalid path `a[0`: missing `]`");
+        assert_eq!(error("a[x]"), "invalid path `a[x]`: index is not a number");
+        assert_eq!(error("a[-1]"), "invalid path `a[-1]`: index is not a number");
+        assert_eq!(error(r#""a"b"#), r#"invalid path `"a"b`: unexpected `b`"#);
+        assert_eq!(error("a[0]b"), "invalid path `a[0]b`: unexpected `b`");
+    }
+
+    #[test]
+    fn get_follows_tables_and_arrays() {
+        let document = document();
+        assert_eq!(get_literal(&document, "postgresql.port").as_deref(), Some("5432"));
+        assert_eq!(get_literal(&document, "postgresql.host").as_deref(), Some(r#""localhost""#));
+        assert_eq!(get_literal(&document, "servers[1].name").as_deref(), Some(r#""beta""#));
+        assert_eq!(get_literal(&document, "servers[0].tags[1]").as_deref(), Some(r#""b""#));
+        assert!(get(document.as_item(), &path("postgresql")).unwrap().is_table());
+        assert!(get(document.as_item(), &path("postgresql.user")).is_none());
+        assert!(get(document.as_item(), &path("servers[2]")).is_none());
+        assert!(get(document.as_item(), &path("postgresql.port.x")).is_none());
+    }
+
+    #[test]
+    fn set_parses_values_and_keeps_strings() {
+        let mut document = document();
+        let mut set = |s: &str, raw: &str| set(document.as_item_mut(), &path(s), raw);
+        set("postgresql.port", "5433").unwrap();
+        set("postgresql.host", "5433").unwrap();
+        set("postgresql.user", "admin").unwrap();
+        set("postgresql.ssl", "true").unwrap();
+        set("servers[0].tags[1]", "c").unwrap();
+        set("redis.pool.size", "8").unwrap();
+
+        assert_eq!(get_literal(&document, "postgresql.port").as_deref(), Some("5433"));
+        assert_eq!(get_literal(&document, "postgresql.host").as_deref(), Some(r#""5433""#));
+        assert_eq!(get_literal(&document, "postgresql.user").as_deref(), Some(r#""admin""#));
+        assert_eq!(get_literal(&document, "postgresql.ssl").as_deref(), Some("true"));
+        assert_eq!(get_literal(&document, "servers[0].tags[1]").as_deref(), Some(r#""c""#));
+        assert!(document.to_string().ends_with("[redis.pool]\nsize = 8\n"));
+    }
+
+    #[test]
+    fn set_reports_what_is_in_the_way() {
+        let mut document = document();
+        let mut set = |s: &str, raw: &str| set(document.as_item_mut(), &path(s), raw).unwrap_err();
+        assert_eq!(set("postgresql", "1"), "`postgresql` is a table, set its keys instead");
+        assert_eq!(set("postgresql.port.x", "1"), "`postgresql.port` is not a table");
+        assert_eq!(set("servers[2].name", "x"), "`servers[2]` not found");
+        assert_eq!(set("servers[0].tags[2]", "x"), "`servers[0].tags[2]` not found");
+        assert_eq!(set("servers[0]", "x"), "`servers` is not an array of values");
+
+        let error = super::set(document.as_item_mut(), &Path::root(), "1").unwrap_err();
+        assert_eq!(error, "cannot set the whole document");
+    }
+
+    #[test]
+    fn delete_removes_values_tables_and_elements() {
+        let mut document = document();
+        delete(document.as_item_mut(), &path("postgresql.host")).unwrap();
+        delete(document.as_item_mut(), &path("servers[0].tags[1]")).unwrap();
+        delete(document.as_item_mut(), &path("servers[1]")).unwrap();
+        assert_eq!(
+            document.to_string(),
+            "# Connection settings\n[postgresql]\nport = 5432\n\n[[servers]]\nname = \"alpha\"\ntags = [\"a\"]\n"
+        );
+
+        delete(document.as_item_mut(), &path("postgresql")).unwrap();
+        assert!(get(document.as_item(), &path("postgresql")).is_none());
+
+        let mut delete = |s: &str| delete(document.as_item_mut(), &path(s)).unwrap_err();
+        assert_eq!(delete("postgresql.port"), "`postgresql.port` not found");
+        assert_eq!(delete("servers[1]"), "`servers[1]` not found");
+        assert_eq!(delete("servers[0].tags[1]"), "`servers[0].tags[1]` not found");
+        assert_eq!(delete("servers[0].name.x"), "`servers[0].name.x` not found");
+    }
+
+    #[test]
+    fn list_walks_tables_and_arrays_of_tables() {
+        let document = document();
+        let listed: Vec<(String, String)> = list(document.as_item(), &Path::root())
+            .iter()
+            .map(|(path, value)| (path.to_string(), literal(value)))
+            .collect();
+        let expected = [
+            ("postgresql.host", r#""localhost""#),
+            ("postgresql.port", "5432"),
+            ("servers[0].name", r#""alpha""#),
+            ("servers[0].tags", r#"["a", "b"]"#),
+            ("servers[1].name", r#""beta""#),
+        ];
+        let expected: Vec<(String, String)> = expected.iter().map(|(p, v)| (p.to_string(), v.to_string())).collect();
+        assert_eq!(listed, expected);
+
+        let servers = list(get(document.as_item(), &path("servers[1]")).unwrap(), &path("servers[1]"));
+        assert_eq!(servers.len(), 1);
+        assert_eq!(servers[0].0, path("servers[1].name"));
+    }
+
+    #[test]
+    fn set_and_delete_keep_the_formatting() {
+        let mut document = document();
+        set(document.as_item_mut(), &path("postgresql.host"), "db.local").unwrap();
+        assert!(document.to_string().contains("host = \"db.local\" # the server\n"));
+
+        set(document.as_item_mut(), &path("postgresql.host"), "localhost").unwrap();
+        set(document.as_item_mut(), &path("postgresql.user"), "admin").unwrap();
+        set(document.as_item_mut(), &path("redis.port"), "6379").unwrap();
+        delete(document.as_item_mut(), &path("postgresql.user")).unwrap();
+        delete(document.as_item_mut(), &path("redis")).unwrap();
+        assert_eq!(document.to_string(), CONFIG);
+    }
+}
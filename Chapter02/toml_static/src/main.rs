@@ -1,78 +1,96 @@
-use clap::Parser;
-use std::fs::File;
-use std::io::Read;
-use toml;
-use serde_derive::Deserialize;
+use clap::{Parser, Subcommand};
+use sales_model::config::{self, Config, LayeredConfig};
 
+/// Reads config.toml, with SALES_SECTION__KEY environment variables and
+/// --set options overriding its values.
 #[derive(Parser, Debug)]
 struct Args {
-    #[arg(long)]
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[arg(long, global = true)]
     config_path: Option<std::path::PathBuf>,
-}
 
-#[allow(unused)]
-#[derive(Deserialize)]
-struct Config {
-    input: Input,
-    redis: Redis,
-    sqlite: SQLite,
-    postgresql: Postgresql,
+    /// Override a config value, e.g. --set postgresql.port=5433
+    #[arg(long = "set", value_name = "KEY=VALUE", global = true)]
+    overrides: Vec<String>,
 }
 
-#[allow(unused)]
-#[derive(Deserialize)]
-struct Input {
-    xml_file: String,
-    json_file: String,
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Inspect the configuration
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
 }
 
-#[allow(unused)]
-#[derive(Deserialize)]
-struct Redis {
-    host: String,
+#[derive(Subcommand, Debug)]
+enum ConfigAction {
+    /// Print the effective configuration, with secrets masked
+    Show {
+        /// Print every value with the layer it came from
+        #[arg(long)]
+        resolved: bool,
+    },
+    /// Check types, required keys and paths, exiting with 1 on problems
+    Validate,
 }
 
-#[allow(unused)]
-#[derive(Deserialize)]
-struct SQLite {
-    db_file: String,
-}
+fn main() {
+    let args = Args::parse();
 
-#[allow(unused)]
-#[derive(Deserialize)]
-struct Postgresql {
-    username: String,
-    password: String,
-    host: String,
-    port: String,
-    database: String,
+    if let Err(e) = run(args) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
 }
 
+fn run(args: Args) -> Result<(), String> {
+    let layered = config::load_config(args.config_path.as_deref(), &args.overrides)?;
 
-fn main() {
-    let args = Args::parse();
-
-    let config_path = match args.config_path {
-        Some(config_path) => config_path,
+    match args.command {
+        Some(Command::Config {
+            action: ConfigAction::Show { resolved },
+        }) => show(&layered, resolved),
+        Some(Command::Config {
+            action: ConfigAction::Validate,
+        }) => validate(&layered),
         None => {
-            println!("No config path provided. Use --config-path to specify a config");
-            return;
-        }
-    };
+            let config: Config = layered.config()?;
+            //println!("{:?}", config);
 
+            println!("[Postgresql].Database: {}", config.postgresql.database);
+            Ok(())
+        }
+    }
+}
 
-    let config: Config = read_toml_dynamically(config_path).unwrap();
-    //println!("{:?}", config);
+fn show(layered: &LayeredConfig, resolved: bool) -> Result<(), String> {
+    // Fail on values of the wrong type before printing anything
+    layered.config()?;
 
-    println!("[Postgresql].Database: {}", config.postgresql.database);
+    if resolved {
+        for (key, value, layer) in layered.entries() {
+            println!("{} = {}  # {}", key, config::display_value(&key, &value), layer);
+        }
+    } else {
+        let contents = toml::to_string(&layered.masked()).map_err(|e| e.to_string())?;
+        print!("{}", contents);
+    }
+    Ok(())
 }
 
-fn read_toml_dynamically(config_path: std::path::PathBuf) -> Result<Config, String> {
-    let file_path = config_path.join("config.toml");
+fn validate(layered: &LayeredConfig) -> Result<(), String> {
+    let issues = layered.validate();
+    for issue in issues.iter() {
+        println!("{}", issue);
+    }
 
-    let mut file = File::open(file_path).map_err(|e| e.to_string())?;
-    let mut contents = String::new();
-    file.read_to_string(&mut contents).map_err(|e| e.to_string())?;
-    toml::from_str(&contents).map_err(|e| e.to_string())
+    if issues.is_empty() {
+        println!("Config is valid");
+        Ok(())
+    } else {
+        Err(format!("{} problems found", issues.len()))
+    }
 }
-
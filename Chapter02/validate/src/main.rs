@@ -0,0 +1,65 @@
+use clap::Parser;
+use sales_model::validate::{validate_raw, RawSale};
+use sales_model::{read_any, Format};
+
+/// Checks a sales file for dangling references, duplicate ids, negative
+/// quantities and unknown units. Exits with status 1 when issues are found.
+#[derive(Parser, Debug)]
+struct Args {
+    #[arg(long)]
+    input_path: Option<std::path::PathBuf>,
+
+    /// Input format, guessed from the input extension when omitted
+    #[arg(long)]
+    from: Option<Format>,
+
+    /// Print the report as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let input_path = match get_input_file(args.input_path, "input-path") {
+        Ok(path) => path,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+    let data = match read_any::<RawSale, _>(&input_path, args.from) {
+        Ok(data) => data,
+        Err(e) => {
+            eprintln!("Could not read {}: {}", input_path.display(), e);
+            std::process::exit(2);
+        }
+    };
+
+    let report = validate_raw(&data);
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+    } else {
+        println!("{}", report);
+    }
+
+    if !report.is_valid() {
+        std::process::exit(1);
+    }
+}
+
+fn get_input_file(
+    option: Option<std::path::PathBuf>,
+    arg_name: &str,
+) -> Result<std::path::PathBuf, String> {
+    match option {
+        Some(path) => {
+            if path.exists() {
+                Ok(path)
+            } else {
+                Err(format!("{} does not exist", arg_name))
+            }
+        }
+        None => Err(format!("{} is required", arg_name)),
+    }
+}
@@ -1,213 +1,98 @@
 use clap::Parser;
-use std::fs::File;
-use std::io::BufReader;
-use xml::reader::{EventReader, XmlEvent};
+use sales_model::config;
+use sales_model::store::{self, Backend, BulkLoad};
+use sales_model::{read_xml, write_xml, xml_records, LoadMode, TimestampFormat};
 
 
 #[derive(Parser, Debug)]
 struct Args {
     #[arg(long)]
     xml_file: Option<std::path::PathBuf>,
-}
 
-#[derive(Debug, Default, Clone)]
-struct Product {
-    id: u32,
-    category: String,
-    name: String,
-}
+    #[arg(long, conflicts_with_all = ["sqlite", "postgres"])]
+    output_path: Option<std::path::PathBuf>,
 
-#[derive(Debug, Default, Clone)]
-struct Sale {
-    id: String,
-    product_id: u32,
-    date: i64,
-    quantity: f64,
-    unit: String,
-}
+    /// Load the records into this SQLite database file as they are read,
+    /// instead of printing them
+    #[arg(long, conflicts_with = "postgres")]
+    sqlite: Option<String>,
 
-enum ParseState {
-    Product,
-    Sale,
-    None,
-}
+    /// Load the records into the Postgres database at this URL as they are
+    /// read, instead of printing them
+    #[arg(long)]
+    postgres: Option<String>,
 
-enum ParseProduct {
-    Id,
-    Category,
-    Name,
-    None,
-}
+    /// insert, upsert (update existing rows) or sync (upsert and delete
+    /// rows missing from the input)
+    #[arg(long, default_value = "insert")]
+    mode: LoadMode,
 
-enum ParseSale {
-    Id,
-    ProductId,
-    Date,
-    Quantity,
-    Unit,
-    None,
+    /// Records written to the database at a time
+    #[arg(long, default_value_t = store::DEFAULT_BATCH_SIZE)]
+    batch_size: usize,
+
+    #[arg(long, default_value = "binary", help = BulkLoad::HELP)]
+    bulk_load: BulkLoad,
+
+    /// Config file, or a directory holding config.toml, with the [quantity]
+    /// rounding rules
+    #[arg(long)]
+    config: Option<std::path::PathBuf>,
 }
 
 fn main() {
-    let mut products = vec![];
-    let mut sales = vec![];
-
     let args = Args::parse();
-    let xml_path = args.xml_file.expect("No XML file provided");
-    let xml_file = File::open(xml_path).expect("Could not open XML file");
-    let file = BufReader::new(xml_file);
-
-    let parser = EventReader::new(file);
-
-    let mut parse_state = ParseState::None;
-    let mut parse_product = ParseProduct::None;
-    let mut parse_sale = ParseSale::None;
-    let mut product = Product::default();
-    let mut sale = Sale::default();
-
-    for event in parser {
-        match &parse_state {
-            ParseState::None => match event {
-                Ok(XmlEvent::StartElement { name, .. }) if name.local_name == "product" => {
-                    parse_state = ParseState::Product;
-                    product = Product::default();
-                    println!("Found product");
-                }
-                Ok(XmlEvent::StartElement { name, .. }) if name.local_name == "sale" => {
-                    parse_state = ParseState::Sale;
-                    sale = Sale::default();
-                    println!("Found sale");
-                },
-                Ok(XmlEvent::EndElement { .. }) => {
-                    println!("End document");
-                    parse_state = ParseState::None;
-                }
-                _ => {}
-            },
-            ParseState::Product => match &parse_product {
-                ParseProduct::None => match event {
-                    Ok(XmlEvent::StartElement { ref name, .. }) if name.local_name == "id" => {
-                        parse_product = ParseProduct::Id;
-                    }
-                    Ok(XmlEvent::StartElement { ref name, .. }) if name.local_name == "category" => {
-                        parse_product = ParseProduct::Category;
-                    }
-                    Ok(XmlEvent::StartElement { ref name, .. }) if name.local_name == "name" => {
-                        parse_product = ParseProduct::Name;
-                    }
-                    Ok(XmlEvent::EndElement { .. }) => {
-                        parse_product = ParseProduct::None;
-                        parse_state = ParseState::None;
-                        products.push(product.clone());
-                        println!("Exit product: {:?}", product);
-                    }
-                    _ => {}
-                },
-                ParseProduct::Id => match event {
-                    Ok(XmlEvent::Characters(ref text)) => {
-                        product.id = text.parse().unwrap();
-                    }
-                    Ok(XmlEvent::EndElement { .. }) => {
-                        parse_product = ParseProduct::None;
-                    }
-                    _ => {}
-                },
-                ParseProduct::Category => match event {
-                    Ok(XmlEvent::Characters(ref text)) => {
-                        product.category = text.to_string();
-                    }
-                    Ok(XmlEvent::EndElement { .. }) => {
-                        parse_product = ParseProduct::None;
-                    }
-                    _ => {}
-                },
-                ParseProduct::Name => match event {
-                    Ok(XmlEvent::Characters(ref text)) => {
-                        product.name = text.to_string();
-                    }
-                    Ok(XmlEvent::EndElement { .. }) => {
-                        parse_product = ParseProduct::None;
-                    }
-                    _ => {}
-                },
-                _ => {}
-            },
-            ParseState::Sale => match &parse_sale {
-                ParseSale::None => match event {
-                    Ok(XmlEvent::StartElement { ref name, .. }) if name.local_name == "id" => {
-                        parse_sale = ParseSale::Id;
-                    }
-                    Ok(XmlEvent::StartElement { ref name, .. }) if name.local_name == "product-id" => {
-                        parse_sale = ParseSale::ProductId;
-                    }
-                    Ok(XmlEvent::StartElement { ref name, .. }) if name.local_name == "date" => {
-                        parse_sale = ParseSale::Date;
-                    }
-                    Ok(XmlEvent::StartElement { ref name, .. }) if name.local_name == "quantity" => {
-                        parse_sale = ParseSale::Quantity;
-                    }
-                    Ok(XmlEvent::StartElement { ref name, .. }) if name.local_name == "unit" => {
-                        parse_sale = ParseSale::Unit;
-                    }
-                    Ok(XmlEvent::EndElement { .. }) => {
-                        parse_sale = ParseSale::None;
-                        parse_state = ParseState::None;
-                        sales.push(sale.clone());
-                        println!("Exit sale: {:?}", sale);
-                    }
-                    _ => {}
-                }
-                ParseSale::Id => match event {
-                    Ok(XmlEvent::Characters(ref text)) => {
-                        sale.id = text.to_string();
-                    }
-                    Ok(XmlEvent::EndElement { .. }) => {
-                        parse_sale = ParseSale::None;
-                    }
-                    _ => {}
-                }
-                ParseSale::ProductId => match event {
-                    Ok(XmlEvent::Characters(ref text)) => {
-                        sale.product_id = text.parse().unwrap();
-                    }
-                    Ok(XmlEvent::EndElement { .. }) => {
-                        parse_sale = ParseSale::None;
-                    }
-                    _ => {}
-                }
-                ParseSale::Date => match event {
-                    Ok(XmlEvent::Characters(ref text)) => {
-                        sale.date = text.parse().unwrap();
-                    }
-                    Ok(XmlEvent::EndElement { .. }) => {
-                        parse_sale = ParseSale::None;
-                    }
-                    _ => {}
-                }
-                ParseSale::Quantity => match event {
-                    Ok(XmlEvent::Characters(ref text)) => {
-                        sale.quantity = text.parse().unwrap();
-                    }
-                    Ok(XmlEvent::EndElement { .. }) => {
-                        parse_sale = ParseSale::None;
-                    }
-                    _ => {}
-                }
-                ParseSale::Unit => match event {
-                    Ok(XmlEvent::Characters(ref text)) => {
-                        sale.unit = text.to_string();
-                    }
-                    Ok(XmlEvent::EndElement { .. }) => {
-                        parse_sale = ParseSale::None;
-                    }
-                    _ => {}
-                }
-                _ => {}
-            }
-            _ => {}
+    let xml_path = args.xml_file.clone().expect("No XML file provided");
+
+    let database = match (args.sqlite.clone(), args.postgres.clone()) {
+        (Some(file), _) => Some((Backend::Sqlite, file)),
+        (_, Some(url)) => Some((Backend::Postgres, url)),
+        _ => None,
+    };
+    if let Some((backend, location)) = database {
+        if let Err(e) = load(&args, &xml_path, backend, &location) {
+            eprintln!("Could not load XML file: {}", e);
+            std::process::exit(1);
         }
+        return;
     }
 
-    println!("Products: {:?}", products);
-    println!("Sales: {:?}", sales);
+    let data = match read_xml(xml_path) {
+        Ok(data) => data,
+        Err(e) => {
+            eprintln!("Could not read XML file: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    for product in data.products.iter() {
+        println!("Found product {:?}", product);
+    }
+    for sale in data.sales.iter() {
+        println!("Found sale {:?}", sale);
+    }
+
+    println!("Products: {:?}", data.products);
+    println!("Sales: {:?}", data.sales);
+
+    if let Some(output_path) = args.output_path {
+        write_xml(output_path, &data, &TimestampFormat::Epoch).expect("Unable to write file");
+    }
+}
+
+// Each element is written as soon as it is read, so the size of the XML
+// file does not matter
+fn load(args: &Args, xml_path: &std::path::Path, backend: Backend, location: &str) -> Result<(), String> {
+    let config = config::load_config(args.config.as_deref(), &[])?.config()?;
+    let records = xml_records(xml_path)?.map(|record| record.map_err(|e| e.to_string()));
+
+    let mut store = store::open_with(backend, location, args.bulk_load)?;
+    store::migrate_up(store.as_mut(), None)?;
+    let (report, rounded) = store::load_records(store.as_mut(), records, &config.quantity, args.mode, args.batch_size)?;
+
+    if rounded > 0 {
+        println!("Rounded {} sale quantities to the scale of their unit", rounded);
+    }
+    println!("{}", report);
+    Ok(())
 }